@@ -0,0 +1,345 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+use portable_atomic::{AtomicU128, AtomicU64};
+
+use crate::compact_id::CompactProcessUniqueId;
+use crate::process_unique_id::ProcessUniqueId;
+
+// Every value stored in these comes from `ProcessUniqueId::to_u128`.
+#[inline]
+fn unpack(value: u128) -> ProcessUniqueId {
+    ProcessUniqueId::from_u128(value).expect("corrupt atomic ID")
+}
+
+/// A `ProcessUniqueId` that can be shared between threads.
+///
+/// This has the same methods as the integer atomics in `std::sync::atomic`. It uses native 128
+/// bit atomic instructions where the target has them (e.g. `cmpxchg16b` on x86_64, detected at
+/// runtime) and falls back to a seqlock otherwise; see `is_lock_free`.
+pub struct AtomicProcessUniqueId(AtomicU128);
+
+impl AtomicProcessUniqueId {
+    /// Create a new atomic ID holding `id`.
+    #[inline]
+    pub const fn new(id: ProcessUniqueId) -> Self {
+        AtomicProcessUniqueId(AtomicU128::new(id.to_u128()))
+    }
+
+    /// Returns true if operations on this type don't take a lock on the current target.
+    #[inline]
+    pub fn is_lock_free() -> bool {
+        AtomicU128::is_lock_free()
+    }
+
+    /// Load the ID.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> ProcessUniqueId {
+        unpack(self.0.load(order))
+    }
+
+    /// Store `id`.
+    #[inline]
+    pub fn store(&self, id: ProcessUniqueId, order: Ordering) {
+        self.0.store(id.to_u128(), order)
+    }
+
+    /// Store `id`, returning the previous ID.
+    #[inline]
+    pub fn swap(&self, id: ProcessUniqueId, order: Ordering) -> ProcessUniqueId {
+        unpack(self.0.swap(id.to_u128(), order))
+    }
+
+    /// Store `new` if the current ID is `current`.
+    ///
+    /// Returns the previous ID, wrapped in `Ok` if it was `current` and `Err` otherwise.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: ProcessUniqueId,
+        new: ProcessUniqueId,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<ProcessUniqueId, ProcessUniqueId> {
+        self.0
+            .compare_exchange(current.to_u128(), new.to_u128(), success, failure)
+            .map(unpack)
+            .map_err(unpack)
+    }
+
+    /// Unwrap the ID.
+    #[inline]
+    pub fn into_inner(self) -> ProcessUniqueId {
+        unpack(self.0.into_inner())
+    }
+}
+
+impl From<ProcessUniqueId> for AtomicProcessUniqueId {
+    #[inline]
+    fn from(id: ProcessUniqueId) -> Self {
+        AtomicProcessUniqueId::new(id)
+    }
+}
+
+impl fmt::Debug for AtomicProcessUniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+/// An `Option<ProcessUniqueId>` that can be shared between threads, e.g. the current owner of
+/// something.
+///
+/// This works like `AtomicProcessUniqueId` and is the same size: `None` is stored as a value no
+/// `ProcessUniqueId` can have.
+pub struct AtomicOptionProcessUniqueId(AtomicU128);
+
+#[inline]
+fn pack_option(id: Option<ProcessUniqueId>) -> u128 {
+    id.map_or(0, ProcessUniqueId::to_u128)
+}
+
+impl AtomicOptionProcessUniqueId {
+    /// Create a new atomic optional ID holding `id`.
+    #[inline]
+    pub const fn new(id: Option<ProcessUniqueId>) -> Self {
+        AtomicOptionProcessUniqueId(AtomicU128::new(match id {
+            Some(id) => id.to_u128(),
+            None => 0,
+        }))
+    }
+
+    /// Returns true if operations on this type don't take a lock on the current target.
+    #[inline]
+    pub fn is_lock_free() -> bool {
+        AtomicU128::is_lock_free()
+    }
+
+    /// Load the ID.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> Option<ProcessUniqueId> {
+        ProcessUniqueId::from_u128(self.0.load(order))
+    }
+
+    /// Store `id`.
+    #[inline]
+    pub fn store(&self, id: Option<ProcessUniqueId>, order: Ordering) {
+        self.0.store(pack_option(id), order)
+    }
+
+    /// Store `id`, returning the previous ID.
+    #[inline]
+    pub fn swap(&self, id: Option<ProcessUniqueId>, order: Ordering) -> Option<ProcessUniqueId> {
+        ProcessUniqueId::from_u128(self.0.swap(pack_option(id), order))
+    }
+
+    /// Store `new` if the current ID is `current`.
+    ///
+    /// Returns the previous ID, wrapped in `Ok` if it was `current` and `Err` otherwise.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: Option<ProcessUniqueId>,
+        new: Option<ProcessUniqueId>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Option<ProcessUniqueId>, Option<ProcessUniqueId>> {
+        self.0
+            .compare_exchange(pack_option(current), pack_option(new), success, failure)
+            .map(ProcessUniqueId::from_u128)
+            .map_err(ProcessUniqueId::from_u128)
+    }
+
+    /// Unwrap the ID.
+    #[inline]
+    pub fn into_inner(self) -> Option<ProcessUniqueId> {
+        ProcessUniqueId::from_u128(self.0.into_inner())
+    }
+}
+
+impl Default for AtomicOptionProcessUniqueId {
+    #[inline]
+    fn default() -> Self {
+        AtomicOptionProcessUniqueId::new(None)
+    }
+}
+
+impl From<Option<ProcessUniqueId>> for AtomicOptionProcessUniqueId {
+    #[inline]
+    fn from(id: Option<ProcessUniqueId>) -> Self {
+        AtomicOptionProcessUniqueId::new(id)
+    }
+}
+
+impl fmt::Debug for AtomicOptionProcessUniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+/// A `CompactProcessUniqueId` that can be shared between threads.
+///
+/// This only needs 64 bit atomics so it's lock free on more targets than
+/// `AtomicProcessUniqueId`.
+pub struct AtomicCompactProcessUniqueId(AtomicU64);
+
+impl AtomicCompactProcessUniqueId {
+    /// Create a new atomic ID holding `id`.
+    #[inline]
+    pub const fn new(id: CompactProcessUniqueId) -> Self {
+        AtomicCompactProcessUniqueId(AtomicU64::new(id.as_u64()))
+    }
+
+    /// Returns true if operations on this type don't take a lock on the current target.
+    #[inline]
+    pub fn is_lock_free() -> bool {
+        AtomicU64::is_lock_free()
+    }
+
+    /// Load the ID.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> CompactProcessUniqueId {
+        CompactProcessUniqueId::from_u64(self.0.load(order))
+    }
+
+    /// Store `id`.
+    #[inline]
+    pub fn store(&self, id: CompactProcessUniqueId, order: Ordering) {
+        self.0.store(id.as_u64(), order)
+    }
+
+    /// Store `id`, returning the previous ID.
+    #[inline]
+    pub fn swap(&self, id: CompactProcessUniqueId, order: Ordering) -> CompactProcessUniqueId {
+        CompactProcessUniqueId::from_u64(self.0.swap(id.as_u64(), order))
+    }
+
+    /// Store `new` if the current ID is `current`.
+    ///
+    /// Returns the previous ID, wrapped in `Ok` if it was `current` and `Err` otherwise.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: CompactProcessUniqueId,
+        new: CompactProcessUniqueId,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<CompactProcessUniqueId, CompactProcessUniqueId> {
+        self.0
+            .compare_exchange(current.as_u64(), new.as_u64(), success, failure)
+            .map(CompactProcessUniqueId::from_u64)
+            .map_err(CompactProcessUniqueId::from_u64)
+    }
+
+    /// Unwrap the ID.
+    #[inline]
+    pub fn into_inner(self) -> CompactProcessUniqueId {
+        CompactProcessUniqueId::from_u64(self.0.into_inner())
+    }
+}
+
+impl From<CompactProcessUniqueId> for AtomicCompactProcessUniqueId {
+    #[inline]
+    fn from(id: CompactProcessUniqueId) -> Self {
+        AtomicCompactProcessUniqueId::new(id)
+    }
+}
+
+impl fmt::Debug for AtomicCompactProcessUniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AtomicCompactProcessUniqueId, AtomicOptionProcessUniqueId, AtomicProcessUniqueId};
+    use crate::compact_id::CompactProcessUniqueId;
+    use crate::process_unique_id::ProcessUniqueId;
+    use std::sync::atomic::Ordering::SeqCst;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_atomic_id() {
+        let (a, b, c) = (
+            ProcessUniqueId::new(),
+            ProcessUniqueId::new(),
+            ProcessUniqueId::from_parts(usize::MAX - 1, u64::MAX),
+        );
+        let atomic = AtomicProcessUniqueId::new(a);
+        assert_eq!(atomic.load(SeqCst), a);
+        atomic.store(b, SeqCst);
+        assert_eq!(atomic.swap(c, SeqCst), b);
+        assert_eq!(atomic.compare_exchange(a, b, SeqCst, SeqCst), Err(c));
+        assert_eq!(atomic.compare_exchange(c, a, SeqCst, SeqCst), Ok(c));
+        assert_eq!(format!("{:?}", atomic), format!("{:?}", a));
+        assert_eq!(atomic.into_inner(), a);
+    }
+
+    #[test]
+    fn test_atomic_option_id() {
+        let a = ProcessUniqueId::from_parts(0, 0);
+        let atomic = AtomicOptionProcessUniqueId::default();
+        assert_eq!(atomic.load(SeqCst), None);
+        assert_eq!(
+            atomic.compare_exchange(None, Some(a), SeqCst, SeqCst),
+            Ok(None)
+        );
+        assert_eq!(
+            atomic.compare_exchange(None, Some(a), SeqCst, SeqCst),
+            Err(Some(a))
+        );
+        assert_eq!(atomic.swap(None, SeqCst), Some(a));
+        atomic.store(Some(a), SeqCst);
+        assert_eq!(atomic.into_inner(), Some(a));
+    }
+
+    #[test]
+    fn test_atomic_compact_id() {
+        let a = CompactProcessUniqueId::new();
+        let b = CompactProcessUniqueId::new();
+        let atomic = AtomicCompactProcessUniqueId::new(a);
+        assert_eq!(atomic.compare_exchange(b, a, SeqCst, SeqCst), Err(a));
+        assert_eq!(atomic.compare_exchange(a, b, SeqCst, SeqCst), Ok(a));
+        assert_eq!(atomic.swap(a, SeqCst), b);
+        atomic.store(b, SeqCst);
+        assert_eq!(atomic.load(SeqCst), b);
+    }
+
+    #[test]
+    fn test_ownership() {
+        // Threads take turns claiming the slot; every claim and release must pair up.
+        let owner = Arc::new(AtomicOptionProcessUniqueId::new(None));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let owner = owner.clone();
+                thread::spawn(move || {
+                    let me = ProcessUniqueId::new();
+                    let mut claims = 0;
+                    while claims < 1000 {
+                        if owner
+                            .compare_exchange(None, Some(me), SeqCst, SeqCst)
+                            .is_ok()
+                        {
+                            assert_eq!(owner.load(SeqCst), Some(me));
+                            assert_eq!(owner.swap(None, SeqCst), Some(me));
+                            claims += 1;
+                        }
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(owner.load(SeqCst), None);
+    }
+}
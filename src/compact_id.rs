@@ -0,0 +1,212 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+#[cfg(feature = "std")]
+use core::cell::UnsafeCell;
+
+use core::fmt;
+#[cfg(not(feature = "std"))]
+use portable_atomic::AtomicU64;
+use portable_atomic::{AtomicU32, Ordering};
+
+use crate::process_unique_id::{LazyPrefix, ProcessUniqueId};
+
+const OFFSET_BITS: u32 = 44;
+const MAX_OFFSET: u64 = (1 << OFFSET_BITS) - 1;
+const PREFIX_LIMIT: u32 = 1 << (64 - OFFSET_BITS);
+
+static COMPACT_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+// The wide prefix every compact ID maps to (see `From<CompactProcessUniqueId>`).
+static WIDE_PREFIX: LazyPrefix = LazyPrefix::new();
+
+fn next_compact_prefix() -> u64 {
+    let prefix = COMPACT_COUNTER
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |prefix| {
+            if prefix < PREFIX_LIMIT {
+                Some(prefix + 1)
+            } else {
+                None
+            }
+        })
+        .expect("Snow Crash: Go home and reevaluate your threading model!");
+    u64::from(prefix) << OFFSET_BITS
+}
+
+// NOTE: We could use a Cell (not unsafe) but this is slightly faster.
+#[cfg(feature = "std")]
+thread_local! {
+    static NEXT_LOCAL_COMPACT_ID: UnsafeCell<u64> = UnsafeCell::new(next_compact_prefix())
+}
+
+// Without thread locals, every thread bumps a shared ID. This holds the last ID handed out and
+// starts out looking like the end of a prefix so the first call reserves a real one.
+#[cfg(not(feature = "std"))]
+static LAST_SHARED_COMPACT_ID: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// A process unique ID that fits in a single `u64`.
+///
+/// This works just like `ProcessUniqueId` (including the thread local fast path) but with a 20
+/// bit prefix and a 44 bit offset, so it's half the size on 64 bit targets.
+///
+/// # Limits
+///
+/// Each thread that creates at least one compact ID reserves 2^44 IDs and there are only 2^20
+/// prefixes to go around. Once every prefix has been handed out, `new()` panics rather than
+/// reuse an ID. That is, don't create compact IDs from over a million different threads (or
+/// create more than 2^64 of them).
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct CompactProcessUniqueId(u64);
+
+impl CompactProcessUniqueId {
+    /// Create a new unique ID.
+    ///
+    /// **panics** if there are no more unique IDs available. If this happens, go home and
+    /// reevaluate your threading model!
+    #[inline]
+    pub fn new() -> Self {
+        #[cfg(feature = "std")]
+        {
+            NEXT_LOCAL_COMPACT_ID.with(|unique_id| unsafe {
+                let next_unique_id = *unique_id.get();
+                *unique_id.get() = if next_unique_id & MAX_OFFSET == MAX_OFFSET {
+                    next_compact_prefix()
+                } else {
+                    next_unique_id + 1
+                };
+                CompactProcessUniqueId(next_unique_id)
+            })
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            let mut last = LAST_SHARED_COMPACT_ID.load(Ordering::Relaxed);
+            // Hold on to a fresh prefix across retries so we don't waste more than we need to.
+            let mut fresh = None;
+            loop {
+                let next = if last & MAX_OFFSET == MAX_OFFSET {
+                    *fresh.get_or_insert_with(next_compact_prefix)
+                } else {
+                    last + 1
+                };
+                match LAST_SHARED_COMPACT_ID.compare_exchange_weak(
+                    last,
+                    next,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => return CompactProcessUniqueId(next),
+                    Err(actual) => last = actual,
+                }
+            }
+        }
+    }
+
+    /// Reinterpret a raw value (from `as_u64`) as a compact ID.
+    #[inline]
+    pub const fn from_u64(raw: u64) -> Self {
+        CompactProcessUniqueId(raw)
+    }
+
+    /// The raw 64 bit value of this ID.
+    #[inline]
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for CompactProcessUniqueId {
+    #[inline]
+    fn default() -> Self {
+        CompactProcessUniqueId::new()
+    }
+}
+
+impl From<CompactProcessUniqueId> for u64 {
+    #[inline]
+    fn from(id: CompactProcessUniqueId) -> u64 {
+        id.0
+    }
+}
+
+/// Widen a compact ID.
+///
+/// All compact IDs map to a single `ProcessUniqueId` prefix, reserved the first time this
+/// conversion is used, with the compact ID's raw value as the offset. The result is therefore
+/// distinct from every other `ProcessUniqueId` (widened or not) and widened IDs compare the same
+/// way their compact counterparts do. The mapping is only stable for the lifetime of the current
+/// process.
+impl From<CompactProcessUniqueId> for ProcessUniqueId {
+    #[inline]
+    fn from(id: CompactProcessUniqueId) -> ProcessUniqueId {
+        ProcessUniqueId::from_parts(WIDE_PREFIX.get(), id.0)
+    }
+}
+
+impl fmt::Display for CompactProcessUniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cpuid-{:x}-{:x}",
+            self.0 >> OFFSET_BITS,
+            self.0 & MAX_OFFSET
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CompactProcessUniqueId;
+    #[cfg(feature = "std")]
+    use super::{MAX_OFFSET, OFFSET_BITS};
+    use crate::process_unique_id::ProcessUniqueId;
+    #[cfg(feature = "std")]
+    use std::mem::size_of;
+    use std::thread;
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_compact_unique_id_unthreaded() {
+        assert_eq!(size_of::<CompactProcessUniqueId>(), 8);
+
+        let first = CompactProcessUniqueId::new();
+        {
+            use super::NEXT_LOCAL_COMPACT_ID;
+            NEXT_LOCAL_COMPACT_ID
+                .with(|unique_id| unsafe { *unique_id.get() = first.0 | (MAX_OFFSET - 1) });
+        }
+        assert_eq!(CompactProcessUniqueId::new().0, first.0 | (MAX_OFFSET - 1));
+        assert_eq!(CompactProcessUniqueId::new().0, first.0 | MAX_OFFSET);
+
+        let next = CompactProcessUniqueId::new();
+        assert_ne!(next.0 >> OFFSET_BITS, first.0 >> OFFSET_BITS);
+        assert_eq!(next.0 & MAX_OFFSET, 0);
+    }
+
+    #[test]
+    fn test_compact_unique_id_threaded() {
+        let threads: Vec<_> = (0..10)
+            .map(|_| thread::spawn(CompactProcessUniqueId::new))
+            .collect();
+        let mut results: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        results.sort();
+        let old_len = results.len();
+        results.dedup();
+        assert_eq!(old_len, results.len());
+    }
+
+    #[test]
+    fn test_widen() {
+        let a = CompactProcessUniqueId::new();
+        let b = CompactProcessUniqueId::new();
+        let (wide_a, wide_b) = (ProcessUniqueId::from(a), ProcessUniqueId::from(b));
+        assert!(wide_a < wide_b);
+        assert_ne!(wide_a, ProcessUniqueId::new());
+        assert_eq!(wide_a.prefix(), wide_b.prefix());
+        assert_eq!(wide_a.offset(), a.as_u64());
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::iter::FusedIterator;
+
+use crate::id_range::IdRange;
+use crate::process_unique_id::{exhausted, ExhaustedError, ProcessUniqueId};
+use crate::splitmix::splitmix64;
+
+/// A reproducible source of `ProcessUniqueId`s.
+///
+/// `ProcessUniqueId::new` picks prefixes in whatever order threads happen to ask for them, so the
+/// IDs it creates differ from run to run. A `DeterministicGenerator` derives its prefix from a
+/// seed and a logical thread index instead: the same seed and index always give the same
+/// sequence of IDs, and generators with the same seed but different indices never give the same
+/// ID. Use one generator per logical thread of a test or simulation.
+///
+/// **warning:** these IDs are only unique among generators sharing a seed. They may collide with
+/// IDs from `ProcessUniqueId::new` or from generators with other seeds so don't mix them.
+///
+/// **note:** generators used to XOR the thread index into the mixed seed. Now that `usize::MAX`
+/// isn't a valid prefix, they add it instead (modulo `usize::MAX`) so every seed gives different
+/// IDs than it used to. Regenerate golden files written before this change.
+///
+/// ```
+/// use snowflake::DeterministicGenerator;
+///
+/// let mut a = DeterministicGenerator::new(42, 0);
+/// let mut b = DeterministicGenerator::new(42, 0);
+/// assert_eq!(a.next_id(), b.next_id());
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DeterministicGenerator {
+    prefix: usize,
+    // `None` once every offset has been handed out.
+    next: Option<u64>,
+}
+
+impl DeterministicGenerator {
+    /// Create a generator for the logical thread `thread_index` of a run seeded with `seed`.
+    ///
+    /// **panics** if `thread_index` is `usize::MAX`.
+    pub fn new(seed: u64, thread_index: usize) -> Self {
+        assert!(
+            thread_index < usize::MAX,
+            "thread_index must be less than usize::MAX"
+        );
+        // Mix the seed so nearby seeds give unrelated prefixes.
+        let mut state = seed;
+        let base = splitmix64(&mut state) as usize % usize::MAX;
+        // Add modulo `usize::MAX` (the one value a prefix can't take) so every index gets its own
+        // prefix.
+        let prefix = match base.checked_add(thread_index) {
+            Some(prefix) if prefix < usize::MAX => prefix,
+            _ => thread_index - (usize::MAX - base),
+        };
+        DeterministicGenerator {
+            prefix,
+            next: Some(0),
+        }
+    }
+
+    /// Create the next ID, like `ProcessUniqueId::new`.
+    ///
+    /// **panics** once all 2^64 IDs have been handed out.
+    #[inline]
+    pub fn next_id(&mut self) -> ProcessUniqueId {
+        self.try_next_id().unwrap_or_else(|_| exhausted())
+    }
+
+    /// Create the next ID, failing instead of panicking once all 2^64 IDs have been handed out,
+    /// like `ProcessUniqueId::try_new`.
+    #[inline]
+    pub fn try_next_id(&mut self) -> Result<ProcessUniqueId, ExhaustedError> {
+        let offset = self.next.ok_or(ExhaustedError(()))?;
+        self.next = offset.checked_add(1);
+        Ok(ProcessUniqueId::from_parts(self.prefix, offset))
+    }
+
+    /// Reserve the next `n` IDs at once, like `ProcessUniqueId::reserve`.
+    ///
+    /// **panics** if fewer than `n` IDs are left. Unlike `ProcessUniqueId::reserve`, this can't
+    /// move on to a fresh prefix.
+    #[inline]
+    pub fn reserve(&mut self, n: u64) -> IdRange {
+        let start = match self.next {
+            Some(start) if n <= u64::MAX - start => start,
+            _ => exhausted(),
+        };
+        self.next = Some(start + n);
+        IdRange::new(self.prefix, start, start + n)
+    }
+}
+
+impl Iterator for DeterministicGenerator {
+    type Item = ProcessUniqueId;
+
+    #[inline]
+    fn next(&mut self) -> Option<ProcessUniqueId> {
+        self.try_next_id().ok()
+    }
+}
+
+impl FusedIterator for DeterministicGenerator {}
+
+#[cfg(test)]
+mod test {
+    use super::DeterministicGenerator;
+    use crate::process_unique_id::{ExhaustedError, ProcessUniqueId};
+
+    #[test]
+    fn test_reproducible() {
+        let run = |seed| {
+            (0..4)
+                .flat_map(|t| DeterministicGenerator::new(seed, t).take(10))
+                .collect::<Vec<_>>()
+        };
+        let mut first = run(7);
+        assert_eq!(first, run(7));
+        assert_ne!(first, run(8));
+
+        first.sort();
+        first.dedup();
+        assert_eq!(first.len(), 40);
+    }
+
+    #[test]
+    fn test_prefix_wraps() {
+        let base = DeterministicGenerator::new(5, 0).prefix;
+        let last = DeterministicGenerator::new(5, usize::MAX - 1).prefix;
+        assert_eq!(last, base.wrapping_sub(1));
+        assert_ne!(last, usize::MAX);
+    }
+
+    #[test]
+    fn test_reserve() {
+        let mut gen = DeterministicGenerator::new(1, 2);
+        let a = gen.next_id();
+        let range = gen.reserve(5);
+        let b = gen.next_id();
+        assert_eq!(range.len(), 5);
+        assert_eq!(
+            range.clone().next(),
+            Some(ProcessUniqueId::from_parts(a.prefix(), 1))
+        );
+        assert_eq!(b, ProcessUniqueId::from_parts(a.prefix(), 6));
+    }
+
+    #[test]
+    fn test_exhausted() {
+        let mut gen = DeterministicGenerator::new(3, 0);
+        gen.next = Some(u64::MAX - 1);
+        assert_eq!(gen.try_next_id().unwrap().offset(), u64::MAX - 1);
+        assert_eq!(gen.try_next_id().unwrap().offset(), u64::MAX);
+        assert_eq!(gen.try_next_id(), Err(ExhaustedError(())));
+        assert_eq!(gen.next(), None);
+    }
+}
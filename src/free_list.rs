@@ -0,0 +1,141 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use portable_atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+
+use crate::process_unique_id::ProcessUniqueId;
+
+const SLOTS: usize = 64;
+
+const EMPTY: u8 = 0;
+const WRITING: u8 = 1;
+const FULL: u8 = 2;
+const READING: u8 = 3;
+
+struct Slot {
+    state: AtomicU8,
+    prefix: AtomicUsize,
+    offset: AtomicU64,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Slot {
+            state: AtomicU8::new(EMPTY),
+            prefix: AtomicUsize::new(0),
+            offset: AtomicU64::new(0),
+        }
+    }
+}
+
+/// A fixed size, lock-free pool of partially used prefixes.
+///
+/// Each entry is the next unused ID under some prefix: every ID from there to the end of the
+/// prefix is free for the taking. Entries are claimed by flipping the slot's state with a CAS so
+/// every entry is handed out at most once.
+pub(crate) struct FreeList {
+    slots: [Slot; SLOTS],
+}
+
+impl FreeList {
+    pub(crate) const fn new() -> Self {
+        FreeList {
+            slots: [const { Slot::new() }; SLOTS],
+        }
+    }
+
+    /// Give back the unused IDs starting at `next`.
+    ///
+    /// If the list is full, they're simply never handed out again.
+    pub(crate) fn push(&self, next: ProcessUniqueId) {
+        for slot in &self.slots {
+            if slot.state.load(Ordering::Relaxed) == EMPTY
+                && slot
+                    .state
+                    .compare_exchange(EMPTY, WRITING, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                slot.prefix.store(next.prefix(), Ordering::Relaxed);
+                slot.offset.store(next.offset(), Ordering::Relaxed);
+                slot.state.store(FULL, Ordering::Release);
+                return;
+            }
+        }
+    }
+
+    /// Take back some IDs given back with `push`.
+    pub(crate) fn pop(&self) -> Option<ProcessUniqueId> {
+        for slot in &self.slots {
+            if slot.state.load(Ordering::Relaxed) == FULL
+                && slot
+                    .state
+                    .compare_exchange(FULL, READING, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                let next = ProcessUniqueId::from_parts(
+                    slot.prefix.load(Ordering::Relaxed),
+                    slot.offset.load(Ordering::Relaxed),
+                );
+                slot.state.store(EMPTY, Ordering::Release);
+                return Some(next);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FreeList, SLOTS};
+    use crate::process_unique_id::ProcessUniqueId;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_push_pop() {
+        let list = FreeList::new();
+        assert_eq!(list.pop(), None);
+
+        for i in 0..(SLOTS + 10) {
+            list.push(ProcessUniqueId::from_parts(i, 1));
+        }
+        let mut popped: Vec<_> = (0..SLOTS).map(|_| list.pop().unwrap()).collect();
+        assert_eq!(list.pop(), None);
+        popped.sort();
+        let expected: Vec<_> = (0..SLOTS)
+            .map(|i| ProcessUniqueId::from_parts(i, 1))
+            .collect();
+        assert_eq!(popped, expected);
+    }
+
+    #[test]
+    fn test_threaded() {
+        let list = Arc::new(FreeList::new());
+        let threads: Vec<_> = (0..8)
+            .map(|t| {
+                let list = list.clone();
+                thread::spawn(move || {
+                    let mut taken = Vec::new();
+                    for i in 0..1000 {
+                        list.push(ProcessUniqueId::from_parts(t, i));
+                        taken.extend(list.pop());
+                    }
+                    taken
+                })
+            })
+            .collect();
+        let mut results: Vec<_> = threads
+            .into_iter()
+            .flat_map(|t| t.join().unwrap())
+            .collect();
+        results.extend(std::iter::from_fn(|| list.pop()));
+        assert_eq!(results.len(), 8000);
+        results.sort();
+        results.dedup();
+        assert_eq!(results.len(), 8000);
+    }
+}
@@ -0,0 +1,399 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::convert::TryFrom;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::process_unique_id::{parse_hex, HexError};
+
+/// An index into a slot array paired with the generation of the slot it was handed out for.
+///
+/// Unlike the other IDs in this crate, these reuse values: a `GenerationalIdAllocator` hands a
+/// freed index out again with the next generation so slot arrays (e.g. the entities in an ECS)
+/// stay dense. Old handles to a reused slot compare unequal to new ones and
+/// `GenerationalIdAllocator::is_alive` tells them apart.
+///
+/// These display as `gid-{index}-{generation}` (lowercase hex) and serialize like
+/// `ProcessUniqueId`s: as that string in human readable formats and as an `(index, generation)`
+/// tuple otherwise.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct GenerationalId {
+    index: u32,
+    generation: u32,
+}
+
+impl GenerationalId {
+    /// The slot index.
+    #[inline]
+    pub fn index(self) -> u32 {
+        self.index
+    }
+
+    /// How many times the slot had been reused when this ID was handed out.
+    #[inline]
+    pub fn generation(self) -> u32 {
+        self.generation
+    }
+}
+
+impl fmt::Display for GenerationalId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "gid-{:x}-{:x}", self.index, self.generation)
+    }
+}
+
+/// An error returned when parsing a `GenerationalId` from a string fails.
+///
+/// The only accepted form is the one produced by `Display`: `gid-{index}-{generation}` where
+/// both numbers are lowercase hexadecimal without leading zeros.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ParseGenerationalIdError {
+    /// The string doesn't start with `gid-`.
+    MissingTag,
+    /// There is no `-` between the index and the generation.
+    MissingSeparator,
+    /// The index isn't canonical lowercase hex.
+    InvalidIndex,
+    /// The generation isn't canonical lowercase hex.
+    InvalidGeneration,
+    /// The index doesn't fit in a `u32`.
+    IndexOverflow,
+    /// The generation doesn't fit in a `u32`.
+    GenerationOverflow,
+}
+
+impl fmt::Display for ParseGenerationalIdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ParseGenerationalIdError::MissingTag => "generational ID must start with \"gid-\"",
+            ParseGenerationalIdError::MissingSeparator => {
+                "generational ID is missing the generation"
+            }
+            ParseGenerationalIdError::InvalidIndex => "invalid generational ID index",
+            ParseGenerationalIdError::InvalidGeneration => "invalid generational ID generation",
+            ParseGenerationalIdError::IndexOverflow => "generational ID index is too large",
+            ParseGenerationalIdError::GenerationOverflow => {
+                "generational ID generation is too large"
+            }
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseGenerationalIdError {}
+
+fn parse_u32(s: &str) -> Result<u32, HexError> {
+    u32::try_from(parse_hex(s)?).map_err(|_| HexError::Overflow)
+}
+
+impl FromStr for GenerationalId {
+    type Err = ParseGenerationalIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseGenerationalIdError> {
+        if !s.starts_with("gid-") {
+            return Err(ParseGenerationalIdError::MissingTag);
+        }
+        let rest = &s[4..];
+        let sep = rest
+            .find('-')
+            .ok_or(ParseGenerationalIdError::MissingSeparator)?;
+        let (index, generation) = (&rest[..sep], &rest[sep + 1..]);
+
+        let index = parse_u32(index).map_err(|e| match e {
+            HexError::Invalid => ParseGenerationalIdError::InvalidIndex,
+            HexError::Overflow => ParseGenerationalIdError::IndexOverflow,
+        })?;
+        let generation = parse_u32(generation).map_err(|e| match e {
+            HexError::Invalid => ParseGenerationalIdError::InvalidGeneration,
+            HexError::Overflow => ParseGenerationalIdError::GenerationOverflow,
+        })?;
+        Ok(GenerationalId { index, generation })
+    }
+}
+
+impl<'a> TryFrom<&'a str> for GenerationalId {
+    type Error = ParseGenerationalIdError;
+
+    #[inline]
+    fn try_from(s: &'a str) -> Result<Self, ParseGenerationalIdError> {
+        s.parse()
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+struct Slot {
+    generation: u32,
+    alive: bool,
+}
+
+/// Hands out `GenerationalId`s, reusing the indices of freed IDs.
+///
+/// Freeing an ID bumps its slot's generation so the next ID handed out for that slot differs
+/// from every earlier one. A slot whose generation reaches `u32::MAX` is retired instead of
+/// being reused so generations never wrap around.
+///
+/// ```
+/// use snowflake::GenerationalIdAllocator;
+///
+/// let mut ids = GenerationalIdAllocator::new();
+/// let a = ids.allocate();
+/// assert!(ids.free(a));
+/// let b = ids.allocate();
+/// assert_eq!(a.index(), b.index());
+/// assert!(!ids.is_alive(a));
+/// assert!(ids.is_alive(b));
+/// ```
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub struct GenerationalIdAllocator {
+    slots: Vec<Slot>,
+    // Freed indices that can be handed out again.
+    free: Vec<u32>,
+    len: usize,
+}
+
+#[cfg(feature = "std")]
+impl GenerationalIdAllocator {
+    /// Create an allocator with no slots.
+    #[inline]
+    pub fn new() -> Self {
+        GenerationalIdAllocator::default()
+    }
+
+    /// Hand out an ID, reusing a freed slot if there is one.
+    ///
+    /// **panics** if all 2^32 slots are in use (or retired).
+    pub fn allocate(&mut self) -> GenerationalId {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                let index = u32::try_from(self.slots.len())
+                    .expect("Snow Crash: Go home and reevaluate your entity model!");
+                self.slots.push(Slot {
+                    generation: 0,
+                    alive: false,
+                });
+                index
+            }
+        };
+        let slot = &mut self.slots[index as usize];
+        slot.alive = true;
+        self.len += 1;
+        GenerationalId {
+            index,
+            generation: slot.generation,
+        }
+    }
+
+    /// Free `id` so its slot can be reused.
+    ///
+    /// Returns false (and does nothing) if `id` isn't alive.
+    pub fn free(&mut self, id: GenerationalId) -> bool {
+        if !self.is_alive(id) {
+            return false;
+        }
+        let slot = &mut self.slots[id.index as usize];
+        slot.alive = false;
+        self.len -= 1;
+        if slot.generation < u32::MAX {
+            slot.generation += 1;
+            self.free.push(id.index);
+        }
+        true
+    }
+
+    /// Returns true if `id` was handed out by this allocator and hasn't been freed.
+    #[inline]
+    pub fn is_alive(&self, id: GenerationalId) -> bool {
+        match self.slots.get(id.index as usize) {
+            Some(slot) => slot.alive && slot.generation == id.generation,
+            None => false,
+        }
+    }
+
+    /// The number of live IDs.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if there are no live IDs.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[cfg(feature = "serde_support")]
+mod serde_impl {
+    use core::fmt;
+    use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+    use serde::ser::{Serialize, Serializer};
+
+    use super::GenerationalId;
+
+    impl Serialize for GenerationalId {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if serializer.is_human_readable() {
+                serializer.collect_str(self)
+            } else {
+                (self.index, self.generation).serialize(serializer)
+            }
+        }
+    }
+
+    impl<'de> Deserialize<'de> for GenerationalId {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            if deserializer.is_human_readable() {
+                deserializer.deserialize_str(GenerationalIdVisitor)
+            } else {
+                deserializer.deserialize_tuple(2, GenerationalIdVisitor)
+            }
+        }
+    }
+
+    struct GenerationalIdVisitor;
+
+    impl<'de> Visitor<'de> for GenerationalIdVisitor {
+        type Value = GenerationalId;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a generational ID")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<GenerationalId, E> {
+            v.parse().map_err(E::custom)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<GenerationalId, A::Error> {
+            let index = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            let generation = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+            Ok(GenerationalId { index, generation })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "std")]
+    use super::GenerationalIdAllocator;
+    use super::{GenerationalId, ParseGenerationalIdError};
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_reuse() {
+        let mut ids = GenerationalIdAllocator::new();
+        let a = ids.allocate();
+        let b = ids.allocate();
+        assert_eq!((a.index(), a.generation()), (0, 0));
+        assert_eq!((b.index(), b.generation()), (1, 0));
+        assert_eq!(ids.len(), 2);
+
+        assert!(ids.free(a));
+        assert!(!ids.free(a));
+        assert!(!ids.is_alive(a));
+        let c = ids.allocate();
+        assert_eq!((c.index(), c.generation()), (0, 1));
+        assert_ne!(a, c);
+        assert!(ids.is_alive(c));
+        assert!(ids.is_alive(b));
+        assert!(!ids.free(a));
+        assert_eq!(ids.len(), 2);
+
+        // Never handed out.
+        assert!(!ids.is_alive(GenerationalId {
+            index: 0,
+            generation: 2,
+        }));
+        assert!(!ids.is_alive(GenerationalId {
+            index: 7,
+            generation: 0,
+        }));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_retire() {
+        let mut ids = GenerationalIdAllocator::new();
+        let a = ids.allocate();
+        ids.slots[0].generation = u32::MAX;
+        let old = GenerationalId {
+            index: a.index(),
+            generation: u32::MAX,
+        };
+        assert!(ids.free(old));
+        assert!(ids.is_empty());
+        // The slot is retired so we get a fresh one.
+        assert_eq!(ids.allocate().index(), 1);
+        assert!(!ids.is_alive(old));
+    }
+
+    #[test]
+    fn test_parse() {
+        let id = GenerationalId {
+            index: 0x1a,
+            generation: 2,
+        };
+        assert_eq!(id.to_string(), "gid-1a-2");
+        assert_eq!("gid-1a-2".parse(), Ok(id));
+        let max = GenerationalId {
+            index: u32::MAX,
+            generation: u32::MAX,
+        };
+        assert_eq!(max.to_string().parse(), Ok(max));
+
+        fn parse(s: &str) -> Result<GenerationalId, ParseGenerationalIdError> {
+            s.parse()
+        }
+        assert_eq!(parse("puid-1-2"), Err(ParseGenerationalIdError::MissingTag));
+        assert_eq!(
+            parse("gid-1"),
+            Err(ParseGenerationalIdError::MissingSeparator)
+        );
+        assert_eq!(
+            parse("gid-01-2"),
+            Err(ParseGenerationalIdError::InvalidIndex)
+        );
+        assert_eq!(
+            parse("gid-1-A"),
+            Err(ParseGenerationalIdError::InvalidGeneration)
+        );
+        assert_eq!(
+            parse("gid-100000000-0"),
+            Err(ParseGenerationalIdError::IndexOverflow)
+        );
+        assert_eq!(
+            parse("gid-0-100000000"),
+            Err(ParseGenerationalIdError::GenerationOverflow)
+        );
+    }
+
+    #[cfg(feature = "serde_support")]
+    #[test]
+    fn test_serde() {
+        extern crate bincode;
+        extern crate serde_json;
+
+        let id = GenerationalId {
+            index: 0x1a,
+            generation: 2,
+        };
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, "\"gid-1a-2\"");
+        assert_eq!(serde_json::from_str::<GenerationalId>(&json).unwrap(), id);
+
+        let bytes = bincode::serialize(&id).unwrap();
+        assert_eq!(bytes.len(), 8);
+        assert_eq!(bincode::deserialize::<GenerationalId>(&bytes).unwrap(), id);
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::hash::{BuildHasherDefault, Hasher};
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
+
+#[cfg(feature = "std")]
+use crate::process_unique_id::ProcessUniqueId;
+
+// An odd constant with a good mix of bits (from FxHash).
+const K: u64 = 0x517c_c1b7_2722_0a95;
+
+/// A fast, non-cryptographic hasher for `ProcessUniqueId`s.
+///
+/// IDs are unique by construction so there's nothing to gain from SipHash's collision resistance.
+/// This folds each field into the state with a rotate, an xor, and a multiply. Don't use it for
+/// keys an attacker can choose (e.g. IDs parsed from untrusted input): it makes no attempt to
+/// resist HashDoS.
+///
+/// It works with any key but it's only fast where keys hash as a few integers.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct ProcessUniqueIdHasher {
+    hash: u64,
+}
+
+impl ProcessUniqueIdHasher {
+    #[inline]
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(26) ^ word).wrapping_mul(K);
+    }
+}
+
+impl Hasher for ProcessUniqueIdHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            let mut word = [0; 8];
+            word.copy_from_slice(chunk);
+            self.add(u64::from_le_bytes(word));
+        }
+        let rest = chunks.remainder();
+        if !rest.is_empty() {
+            let mut word = [0; 8];
+            word[..rest.len()].copy_from_slice(rest);
+            self.add(u64::from_le_bytes(word));
+        }
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.add(u64::from(i));
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.add(u64::from(i));
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.add(u64::from(i));
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.add(i);
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.add(i as u64);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// A `BuildHasher` for `ProcessUniqueIdHasher`.
+pub type BuildIdHasher = BuildHasherDefault<ProcessUniqueIdHasher>;
+
+/// A `HashMap` keyed by `ProcessUniqueId`s using `ProcessUniqueIdHasher`.
+///
+/// Create one with `IdHashMap::default()`.
+#[cfg(feature = "std")]
+pub type IdHashMap<V> = HashMap<ProcessUniqueId, V, BuildIdHasher>;
+
+/// A `HashSet` of `ProcessUniqueId`s using `ProcessUniqueIdHasher`.
+///
+/// Create one with `IdHashSet::default()`.
+#[cfg(feature = "std")]
+pub type IdHashSet = HashSet<ProcessUniqueId, BuildIdHasher>;
+
+#[cfg(test)]
+mod test {
+    extern crate test;
+    #[cfg(feature = "std")]
+    use self::test::Bencher;
+    use super::ProcessUniqueIdHasher;
+    #[cfg(feature = "std")]
+    use super::{IdHashMap, IdHashSet};
+    use crate::process_unique_id::ProcessUniqueId;
+    #[cfg(feature = "std")]
+    use std::collections::HashMap;
+    use std::hash::{Hash, Hasher};
+
+    fn hash<T: Hash>(value: T) -> u64 {
+        let mut hasher = ProcessUniqueIdHasher::default();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_spread() {
+        let ids: Vec<_> = (0..4)
+            .flat_map(|prefix| (0..256).map(move |o| ProcessUniqueId::from_parts(prefix, o)))
+            .collect();
+        let mut hashes: Vec<_> = ids.iter().map(hash).collect();
+        hashes.sort();
+        hashes.dedup();
+        assert_eq!(hashes.len(), ids.len());
+
+        // Swapping the fields changes the hash.
+        assert_ne!(
+            hash(ProcessUniqueId::from_parts(1, 2)),
+            hash(ProcessUniqueId::from_parts(2, 1))
+        );
+        // Byte slices pad their last word but still hash differently from shorter ones.
+        assert_ne!(hash(&b"abc"[..]), hash(&b"abc\0"[..]));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_collections() {
+        let ids: Vec<_> = (0..100).map(|_| ProcessUniqueId::new()).collect();
+        let mut map = IdHashMap::default();
+        let mut set = IdHashSet::default();
+        for (i, &id) in ids.iter().enumerate() {
+            map.insert(id, i);
+            set.insert(id);
+        }
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(map[id], i);
+            assert!(set.contains(id));
+        }
+        assert!(!set.contains(&ProcessUniqueId::new()));
+    }
+
+    #[cfg(feature = "std")]
+    fn ids() -> Vec<ProcessUniqueId> {
+        (0..1000).map(|_| ProcessUniqueId::new()).collect()
+    }
+
+    #[cfg(feature = "std")]
+    #[bench]
+    fn bench_lookup_default_hasher(b: &mut Bencher) {
+        let ids = ids();
+        let map: HashMap<_, _> = ids.iter().map(|&id| (id, ())).collect();
+        b.iter(|| ids.iter().filter(|id| map.contains_key(id)).count());
+    }
+
+    #[cfg(feature = "std")]
+    #[bench]
+    fn bench_lookup_id_hasher(b: &mut Bencher) {
+        let ids = ids();
+        let map: IdHashMap<_> = ids.iter().map(|&id| (id, ())).collect();
+        b.iter(|| ids.iter().filter(|id| map.contains_key(id)).count());
+    }
+
+    #[cfg(feature = "std")]
+    #[bench]
+    fn bench_insert_default_hasher(b: &mut Bencher) {
+        let ids = ids();
+        b.iter(|| ids.iter().map(|&id| (id, ())).collect::<HashMap<_, _>>());
+    }
+
+    #[cfg(feature = "std")]
+    #[bench]
+    fn bench_insert_id_hasher(b: &mut Bencher) {
+        let ids = ids();
+        b.iter(|| ids.iter().map(|&id| (id, ())).collect::<IdHashMap<_>>());
+    }
+}
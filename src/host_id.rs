@@ -0,0 +1,252 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::cell::Cell;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use crate::process_unique_id::{exhausted, ExhaustedError};
+
+// The counter is the first 8 bytes of the file.
+const SEGMENT_LEN: u64 = 8;
+
+static HOST_COUNTER: OnceLock<&'static AtomicU64> = OnceLock::new();
+
+thread_local! {
+    static NEXT_LOCAL_HOST_ID: Cell<Option<HostUniqueId>> = const { Cell::new(None) };
+}
+
+/// Map the prefix counter stored in `path`, creating the file if it doesn't exist yet.
+fn map_counter(path: &Path) -> io::Result<&'static AtomicU64> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the host ID counter must be a regular file",
+        ));
+    }
+    // Several processes may race to create the file. That's fine: growing it only ever adds zeros
+    // and growing it to the size it already has leaves a counter someone is using alone.
+    if metadata.len() < SEGMENT_LEN {
+        file.set_len(SEGMENT_LEN)?;
+    }
+    let addr = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            SEGMENT_LEN as usize,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if addr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    // The mapping outlives the file descriptor and is never unmapped. Mappings are page aligned
+    // so the counter is suitably aligned. This has to be a native atomic (not one emulated with a
+    // lock) as other processes update it too.
+    Ok(unsafe { &*(addr as *const AtomicU64) })
+}
+
+fn next_host_prefix(counter: &AtomicU64) -> Result<u64, ExhaustedError> {
+    counter
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |prefix| {
+            prefix.checked_add(1)
+        })
+        .map_err(|_| ExhaustedError(()))
+}
+
+/// A unique ID shared between the processes on a host.
+///
+/// These work like `ProcessUniqueId`s (a prefix reserved per thread plus a thread local offset)
+/// except that prefixes come from a counter stored in a file that every cooperating process maps
+/// into memory. Put the file on a `tmpfs` such as `/dev/shm` (where it's effectively a POSIX
+/// shared memory segment) or anywhere else all the processes can read and write.
+///
+/// Call `HostUniqueId::init` with the path of the counter file once per process before creating
+/// any IDs. IDs are unique among all processes using the same file for as long as the file
+/// exists; deleting it starts the counter over.
+///
+/// ```no_run
+/// use snowflake::HostUniqueId;
+///
+/// HostUniqueId::init("/dev/shm/my-service-ids").expect("failed to map the ID counter");
+/// let id = HostUniqueId::new();
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct HostUniqueId {
+    prefix: u64,
+    offset: u64,
+}
+
+impl HostUniqueId {
+    /// Use the counter in the file at `path` (created if needed) for this process's IDs.
+    ///
+    /// Fails if the file can't be opened for reading and writing (e.g. it belongs to another user),
+    /// isn't a regular file, or can't be mapped, and with `io::ErrorKind::AlreadyExists` if this
+    /// process has already called `init`.
+    pub fn init<P: AsRef<Path>>(path: P) -> io::Result<()> {
+        let already_initialized = || {
+            io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "HostUniqueId::init was already called",
+            )
+        };
+        if HOST_COUNTER.get().is_some() {
+            return Err(already_initialized());
+        }
+        let counter = map_counter(path.as_ref())?;
+        HOST_COUNTER.set(counter).map_err(|_| already_initialized())
+    }
+
+    /// Create a new unique ID.
+    ///
+    /// **panics** if `init` hasn't been called or if there are no more unique IDs available.
+    #[inline]
+    pub fn new() -> Self {
+        HostUniqueId::try_new().unwrap_or_else(|_| exhausted())
+    }
+
+    /// Create a new unique ID, failing instead of panicking if there are no more unique IDs
+    /// available.
+    ///
+    /// **panics** if `init` hasn't been called.
+    #[inline]
+    pub fn try_new() -> Result<Self, ExhaustedError> {
+        let counter = HOST_COUNTER
+            .get()
+            .expect("HostUniqueId::init must be called before creating IDs");
+        NEXT_LOCAL_HOST_ID.with(|next| {
+            let id = match next.get() {
+                Some(id) => id,
+                None => HostUniqueId {
+                    prefix: next_host_prefix(counter)?,
+                    offset: 0,
+                },
+            };
+            next.set(if id.offset == u64::MAX {
+                None
+            } else {
+                Some(HostUniqueId {
+                    prefix: id.prefix,
+                    offset: id.offset + 1,
+                })
+            });
+            Ok(id)
+        })
+    }
+
+    /// The prefix reserved from the shared counter.
+    #[inline]
+    pub fn prefix(self) -> u64 {
+        self.prefix
+    }
+
+    /// The offset within the prefix.
+    #[inline]
+    pub fn offset(self) -> u64 {
+        self.offset
+    }
+}
+
+impl Default for HostUniqueId {
+    #[inline]
+    fn default() -> Self {
+        HostUniqueId::new()
+    }
+}
+
+impl fmt::Display for HostUniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "hpuid-{:x}-{:x}", self.prefix, self.offset)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{map_counter, next_host_prefix, HostUniqueId};
+    use std::env;
+    use std::fs;
+    use std::io;
+    use std::path::PathBuf;
+    use std::process;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("snowflake-{}-{}", process::id(), name))
+    }
+
+    #[test]
+    fn test_shared_counter() {
+        let path = temp_path("shared");
+        let _ = fs::remove_file(&path);
+
+        // Two mappings of the same file stand in for two processes.
+        let a = map_counter(&path).unwrap();
+        let b = map_counter(&path).unwrap();
+        assert!(!std::ptr::eq(a, b));
+        assert_eq!(next_host_prefix(a), Ok(0));
+        assert_eq!(next_host_prefix(b), Ok(1));
+        assert_eq!(next_host_prefix(a), Ok(2));
+        assert_eq!(fs::metadata(&path).unwrap().len(), 8);
+
+        // Reopening an existing file keeps the count.
+        let c = map_counter(&path).unwrap();
+        assert_eq!(c.load(Ordering::Relaxed), 3);
+
+        a.store(u64::MAX, Ordering::Relaxed);
+        assert!(next_host_prefix(b).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let missing = temp_path("missing-dir").join("counter");
+        assert_eq!(
+            map_counter(&missing).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert!(map_counter(&env::temp_dir()).is_err());
+    }
+
+    #[test]
+    fn test_new() {
+        let path = temp_path("global");
+        let _ = fs::remove_file(&path);
+        HostUniqueId::init(&path).unwrap();
+        assert_eq!(
+            HostUniqueId::init(&path).unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+
+        let a = HostUniqueId::new();
+        let b = HostUniqueId::new();
+        assert_eq!(b.prefix(), a.prefix());
+        assert_eq!(b.offset(), a.offset() + 1);
+        let c = thread::spawn(HostUniqueId::new).join().unwrap();
+        assert_ne!(c.prefix(), a.prefix());
+        assert_eq!(c.to_string(), format!("hpuid-{:x}-0", c.prefix()));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+use crate::free_list::FreeList;
+use crate::id_range::IdRange;
+use crate::process_unique_id::{
+    exhausted, reserve_from, try_advance, ExhaustedError, ProcessUniqueId, GLOBAL_COUNTER,
+};
+
+// Prefixes given back by dropped generators (including the ones in exited threads' thread locals).
+static FREE_PREFIXES: FreeList = FreeList::new();
+
+/// An owned source of `ProcessUniqueId`s.
+///
+/// This is exactly what `ProcessUniqueId::new` keeps in a thread local, but as a value you can
+/// store wherever you like: in a struct, in another thread local, or on the stack of an at-exit
+/// hook. IDs from generators and from `ProcessUniqueId::new` are all unique with respect to each
+/// other.
+///
+/// A generator claims a prefix the first time it creates an ID and gives back whatever is left
+/// of it when dropped so the next generator (or thread) can carry on from there. Generators can
+/// be moved between threads but not shared:
+///
+/// ```compile_fail
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<snowflake::IdGenerator>();
+/// ```
+#[derive(Debug)]
+pub struct IdGenerator {
+    // `None` until we claim a prefix and again once it runs out.
+    next: Option<ProcessUniqueId>,
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+impl IdGenerator {
+    /// Create a new generator. This doesn't claim a prefix until the first ID is created.
+    #[inline]
+    pub const fn new() -> Self {
+        IdGenerator {
+            next: None,
+            _not_sync: PhantomData,
+        }
+    }
+
+    /// The next ID to hand out, resuming a prefix given back by another generator if we need a
+    /// new one.
+    #[inline]
+    fn next_mut(&mut self) -> &mut Option<ProcessUniqueId> {
+        if self.next.is_none() {
+            self.next = FREE_PREFIXES.pop();
+        }
+        &mut self.next
+    }
+
+    /// Replace the next ID to hand out, dropping the current one without giving it back.
+    #[cfg(test)]
+    pub(crate) fn set_next(&mut self, next: Option<ProcessUniqueId>) {
+        self.next = next;
+    }
+
+    /// Create a new unique ID.
+    ///
+    /// **panics** if there are no more unique IDs available.
+    #[inline]
+    pub fn next_id(&mut self) -> ProcessUniqueId {
+        self.try_next_id().unwrap_or_else(|_| exhausted())
+    }
+
+    /// Create a new unique ID, failing instead of panicking if there are no more unique IDs
+    /// available.
+    #[inline]
+    pub fn try_next_id(&mut self) -> Result<ProcessUniqueId, ExhaustedError> {
+        try_advance(self.next_mut(), &GLOBAL_COUNTER)
+    }
+
+    /// Reserve `n` consecutive unique IDs at once, like `ProcessUniqueId::reserve`.
+    ///
+    /// **panics** if there are no more unique IDs available.
+    #[inline]
+    pub fn reserve(&mut self, n: u64) -> IdRange {
+        reserve_from(self.next_mut(), n, &GLOBAL_COUNTER)
+    }
+}
+
+impl Default for IdGenerator {
+    #[inline]
+    fn default() -> Self {
+        IdGenerator::new()
+    }
+}
+
+impl Drop for IdGenerator {
+    fn drop(&mut self) {
+        // Nobody else will ever hand out the rest of this prefix so let someone else have it.
+        if let Some(next) = self.next {
+            FREE_PREFIXES.push(next);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IdGenerator;
+    use crate::process_unique_id::{next_prefix, ProcessUniqueId, GLOBAL_COUNTER};
+    use std::cell::RefCell;
+    use std::sync::mpsc::{channel, Sender};
+    use std::thread;
+
+    #[test]
+    fn test_generator() {
+        // Start on a fresh prefix: one given back by another test might not have room for the
+        // offsets below.
+        let mut gen = IdGenerator::new();
+        gen.set_next(Some(ProcessUniqueId::from_parts(
+            next_prefix(&GLOBAL_COUNTER),
+            0,
+        )));
+        let a = gen.next_id();
+        let b = gen.try_next_id().unwrap();
+        assert_eq!(b.prefix(), a.prefix());
+        assert_eq!(b.offset(), a.offset() + 1);
+        let range = gen.reserve(3);
+        assert_eq!(gen.next_id().offset(), a.offset() + 5);
+
+        // Generators don't share prefixes with each other or with the thread local one.
+        let mut other = IdGenerator::new();
+        let c = other.next_id();
+        assert_ne!(c.prefix(), a.prefix());
+        assert_ne!(ProcessUniqueId::new().prefix(), a.prefix());
+
+        // Move it to another thread and keep going.
+        let d = thread::spawn(move || gen.next_id()).join().unwrap();
+        assert!(!range.contains(&d));
+        assert!(d > a);
+    }
+
+    #[test]
+    fn test_during_tls_destruction() {
+        struct CreateOnDrop(RefCell<Option<Sender<ProcessUniqueId>>>);
+
+        impl Drop for CreateOnDrop {
+            fn drop(&mut self) {
+                let tx = self.0.get_mut().take().unwrap();
+                tx.send(ProcessUniqueId::new()).unwrap();
+                tx.send(ProcessUniqueId::new()).unwrap();
+            }
+        }
+
+        thread_local! {
+            static CREATE_ON_DROP: CreateOnDrop = const { CreateOnDrop(RefCell::new(None)) };
+        }
+
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            CREATE_ON_DROP.with(|c| *c.0.borrow_mut() = Some(tx));
+            // Thread locals are usually destroyed in the reverse order they were created in so
+            // the ID thread local should be gone by the time `CreateOnDrop` runs.
+            ProcessUniqueId::new();
+        })
+        .join()
+        .unwrap();
+        let ids: Vec<ProcessUniqueId> = rx.iter().collect();
+        assert_eq!(ids.len(), 2);
+        assert_ne!(ids[0], ids[1]);
+    }
+}
@@ -0,0 +1,500 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A map keyed by `ProcessUniqueId` that stores runs of consecutive IDs densely.
+use std::collections::{btree_set, BTreeSet, HashMap};
+use std::fmt;
+use std::iter::{FromIterator, FusedIterator};
+
+use crate::hasher::BuildIdHasher;
+use crate::process_unique_id::ProcessUniqueId;
+
+const CHUNK_BITS: u32 = 6;
+const CHUNK_LEN: usize = 1 << CHUNK_BITS;
+
+// A chunk is identified by its ID's prefix and the offset shifted right by `CHUNK_BITS`.
+type ChunkKey = (usize, u64);
+
+#[inline]
+fn split(id: &ProcessUniqueId) -> (ChunkKey, usize) {
+    let offset = id.offset();
+    (
+        (id.prefix(), offset >> CHUNK_BITS),
+        (offset as usize) & (CHUNK_LEN - 1),
+    )
+}
+
+#[inline]
+fn join(key: ChunkKey, slot: usize) -> ProcessUniqueId {
+    ProcessUniqueId::from_parts(key.0, key.1 << CHUNK_BITS | slot as u64)
+}
+
+#[derive(Clone)]
+struct Chunk<V> {
+    slots: Box<[Option<V>]>,
+    // Bit `i` is set if `slots[i]` is occupied.
+    occupied: u64,
+}
+
+impl<V> Chunk<V> {
+    fn new() -> Self {
+        Chunk {
+            slots: (0..CHUNK_LEN).map(|_| None).collect(),
+            occupied: 0,
+        }
+    }
+}
+
+/// A map from `ProcessUniqueId`s to values.
+///
+/// A thread hands out IDs under one prefix with consecutive offsets so the IDs a program creates
+/// tend to come in long runs. `IdMap` stores values in chunks of 64 consecutive offsets and finds
+/// chunks with a hash table (using `ProcessUniqueIdHasher`) so runs of IDs are stored compactly
+/// and `get` takes constant time. Sparse keys (e.g. IDs from many short lived threads) waste
+/// space, up to a whole chunk per key.
+///
+/// Unlike a `HashMap`, iteration is in key order. For this, the map also keeps the chunks in an
+/// ordered set which only changes when a chunk is created or freed. So `insert` and `remove` take
+/// constant time, except when they create or free a chunk (at most once per 64 consecutive IDs)
+/// which takes `O(log c)` time for `c` chunks. With sparse keys, that's every time.
+///
+/// ```
+/// use snowflake::{IdMap, ProcessUniqueId};
+///
+/// let mut map = IdMap::new();
+/// let a = ProcessUniqueId::new();
+/// let b = ProcessUniqueId::new();
+/// map.insert(b, "b");
+/// *map.entry(a).or_insert("") = "a";
+/// assert_eq!(map.iter().collect::<Vec<_>>(), vec![(a, &"a"), (b, &"b")]);
+/// ```
+#[derive(Clone)]
+pub struct IdMap<V> {
+    chunks: HashMap<ChunkKey, Chunk<V>, BuildIdHasher>,
+    // The keys of `chunks`, in order, for iteration. Only updated when a chunk is created or freed.
+    order: BTreeSet<ChunkKey>,
+    len: usize,
+}
+
+impl<V> IdMap<V> {
+    /// Create an empty map.
+    #[inline]
+    pub fn new() -> Self {
+        IdMap {
+            chunks: HashMap::default(),
+            order: BTreeSet::new(),
+            len: 0,
+        }
+    }
+
+    /// The number of entries in the map.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the map has no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Remove every entry.
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.order.clear();
+        self.len = 0;
+    }
+
+    /// The value stored under `id`, if any.
+    #[inline]
+    pub fn get(&self, id: &ProcessUniqueId) -> Option<&V> {
+        let (key, slot) = split(id);
+        self.chunks.get(&key)?.slots[slot].as_ref()
+    }
+
+    /// The value stored under `id`, if any.
+    #[inline]
+    pub fn get_mut(&mut self, id: &ProcessUniqueId) -> Option<&mut V> {
+        let (key, slot) = split(id);
+        self.chunks.get_mut(&key)?.slots[slot].as_mut()
+    }
+
+    /// Returns true if there is a value stored under `id`.
+    #[inline]
+    pub fn contains_key(&self, id: &ProcessUniqueId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Store `value` under `id`, returning the value it replaces, if any.
+    pub fn insert(&mut self, id: ProcessUniqueId, value: V) -> Option<V> {
+        let (key, slot) = split(&id);
+        let order = &mut self.order;
+        let chunk = self.chunks.entry(key).or_insert_with(|| {
+            order.insert(key);
+            Chunk::new()
+        });
+        let old = chunk.slots[slot].replace(value);
+        if old.is_none() {
+            chunk.occupied |= 1 << slot;
+            self.len += 1;
+        }
+        old
+    }
+
+    /// Remove and return the value stored under `id`, if any.
+    pub fn remove(&mut self, id: &ProcessUniqueId) -> Option<V> {
+        let (key, slot) = split(id);
+        let chunk = self.chunks.get_mut(&key)?;
+        let old = chunk.slots[slot].take()?;
+        chunk.occupied &= !(1 << slot);
+        self.len -= 1;
+        if chunk.occupied == 0 {
+            self.chunks.remove(&key);
+            self.order.remove(&key);
+        }
+        Some(old)
+    }
+
+    /// The entry for `id`, for in-place manipulation.
+    #[inline]
+    pub fn entry(&mut self, id: ProcessUniqueId) -> Entry<'_, V> {
+        if self.contains_key(&id) {
+            Entry::Occupied(OccupiedEntry { map: self, id })
+        } else {
+            Entry::Vacant(VacantEntry { map: self, id })
+        }
+    }
+
+    /// Iterate over the entries in key order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, V> {
+        Iter {
+            chunks: &self.chunks,
+            order: self.order.iter(),
+            current: None,
+            remaining: self.len,
+        }
+    }
+}
+
+impl<V> Default for IdMap<V> {
+    #[inline]
+    fn default() -> Self {
+        IdMap::new()
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for IdMap<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<V> Extend<(ProcessUniqueId, V)> for IdMap<V> {
+    fn extend<I: IntoIterator<Item = (ProcessUniqueId, V)>>(&mut self, iter: I) {
+        for (id, value) in iter {
+            self.insert(id, value);
+        }
+    }
+}
+
+impl<V> FromIterator<(ProcessUniqueId, V)> for IdMap<V> {
+    fn from_iter<I: IntoIterator<Item = (ProcessUniqueId, V)>>(iter: I) -> Self {
+        let mut map = IdMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<'a, V> IntoIterator for &'a IdMap<V> {
+    type Item = (ProcessUniqueId, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    #[inline]
+    fn into_iter(self) -> Iter<'a, V> {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of an `IdMap`, in key order.
+pub struct Iter<'a, V> {
+    chunks: &'a HashMap<ChunkKey, Chunk<V>, BuildIdHasher>,
+    order: btree_set::Iter<'a, ChunkKey>,
+    // The current chunk and the slots in it we haven't visited yet.
+    current: Option<(ChunkKey, &'a Chunk<V>, u64)>,
+    remaining: usize,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (ProcessUniqueId, &'a V);
+
+    fn next(&mut self) -> Option<(ProcessUniqueId, &'a V)> {
+        loop {
+            if let Some((key, chunk, ref mut left)) = self.current {
+                if *left != 0 {
+                    let slot = left.trailing_zeros() as usize;
+                    *left &= *left - 1;
+                    self.remaining -= 1;
+                    return Some((join(key, slot), chunk.slots[slot].as_ref().unwrap()));
+                }
+            }
+            let key = *self.order.next()?;
+            let chunk = &self.chunks[&key];
+            self.current = Some((key, chunk, chunk.occupied));
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<V> ExactSizeIterator for Iter<'_, V> {}
+
+impl<V> FusedIterator for Iter<'_, V> {}
+
+/// An entry in an `IdMap`, returned by `IdMap::entry`.
+pub enum Entry<'a, V> {
+    /// There is a value stored under the ID.
+    Occupied(OccupiedEntry<'a, V>),
+    /// There is no value stored under the ID.
+    Vacant(VacantEntry<'a, V>),
+}
+
+impl<'a, V> Entry<'a, V> {
+    /// The ID of this entry.
+    #[inline]
+    pub fn key(&self) -> ProcessUniqueId {
+        match *self {
+            Entry::Occupied(ref entry) => entry.id,
+            Entry::Vacant(ref entry) => entry.id,
+        }
+    }
+
+    /// Insert `default` if the entry is vacant and return the entry's value.
+    #[inline]
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    /// Insert the result of `default` if the entry is vacant and return the entry's value.
+    #[inline]
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Call `f` on the value if the entry is occupied.
+    #[inline]
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(ref mut entry) = self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, V: Default> Entry<'a, V> {
+    /// Insert `V::default()` if the entry is vacant and return the entry's value.
+    #[inline]
+    pub fn or_default(self) -> &'a mut V {
+        self.or_insert_with(V::default)
+    }
+}
+
+/// An occupied entry in an `IdMap`.
+pub struct OccupiedEntry<'a, V> {
+    map: &'a mut IdMap<V>,
+    id: ProcessUniqueId,
+}
+
+// The entry's slot is occupied for as long as the entry exists.
+impl<'a, V> OccupiedEntry<'a, V> {
+    /// The ID of this entry.
+    #[inline]
+    pub fn key(&self) -> ProcessUniqueId {
+        self.id
+    }
+
+    /// The value stored in this entry.
+    #[inline]
+    pub fn get(&self) -> &V {
+        self.map.get(&self.id).unwrap()
+    }
+
+    /// The value stored in this entry.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut V {
+        self.map.get_mut(&self.id).unwrap()
+    }
+
+    /// The value stored in this entry, borrowed for as long as the map was.
+    #[inline]
+    pub fn into_mut(self) -> &'a mut V {
+        self.map.get_mut(&self.id).unwrap()
+    }
+
+    /// Replace the value stored in this entry, returning the old one.
+    #[inline]
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    /// Remove this entry from the map, returning its value.
+    #[inline]
+    pub fn remove(self) -> V {
+        self.map.remove(&self.id).unwrap()
+    }
+}
+
+/// A vacant entry in an `IdMap`.
+pub struct VacantEntry<'a, V> {
+    map: &'a mut IdMap<V>,
+    id: ProcessUniqueId,
+}
+
+impl<'a, V> VacantEntry<'a, V> {
+    /// The ID of this entry.
+    #[inline]
+    pub fn key(&self) -> ProcessUniqueId {
+        self.id
+    }
+
+    /// Store `value` in this entry and return a reference to it.
+    #[inline]
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.map.insert(self.id, value);
+        self.map.get_mut(&self.id).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate test;
+    use self::test::Bencher;
+    use super::{Entry, IdMap};
+    use crate::process_unique_id::ProcessUniqueId;
+    use std::collections::BTreeMap;
+
+    fn id(prefix: usize, offset: u64) -> ProcessUniqueId {
+        ProcessUniqueId::from_parts(prefix, offset)
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut map = IdMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.insert(id(1, 5), "a"), None);
+        assert_eq!(map.insert(id(1, 5), "b"), Some("a"));
+        assert_eq!(map.insert(id(1, u64::MAX), "c"), None);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&id(1, 5)), Some(&"b"));
+        assert_eq!(map.get(&id(1, 6)), None);
+        assert_eq!(map.get(&id(2, 5)), None);
+        *map.get_mut(&id(1, u64::MAX)).unwrap() = "d";
+
+        assert_eq!(map.remove(&id(1, 6)), None);
+        assert_eq!(map.remove(&id(1, 5)), Some("b"));
+        assert_eq!(map.remove(&id(1, 5)), None);
+        assert_eq!(map.remove(&id(1, u64::MAX)), Some("d"));
+        assert!(map.is_empty());
+        // Empty chunks are freed.
+        assert!(map.chunks.is_empty());
+        assert!(map.order.is_empty());
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut map = IdMap::new();
+        *map.entry(id(0, 1)).or_insert(0) += 1;
+        *map.entry(id(0, 1)).or_insert(0) += 1;
+        map.entry(id(0, 2)).and_modify(|v| *v = 100).or_default();
+        assert_eq!(map.get(&id(0, 1)), Some(&2));
+        assert_eq!(map.get(&id(0, 2)), Some(&0));
+
+        match map.entry(id(0, 1)) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), id(0, 1));
+                assert_eq!(entry.insert(7), 2);
+                assert_eq!(entry.remove(), 7);
+            }
+            Entry::Vacant(_) => panic!("expected an occupied entry"),
+        }
+        match map.entry(id(0, 1)) {
+            Entry::Vacant(entry) => assert_eq!(*entry.insert(3), 3),
+            Entry::Occupied(_) => panic!("expected a vacant entry"),
+        }
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_iter_order() {
+        let mut ids = Vec::new();
+        for prefix in &[3, 0, usize::MAX - 1, 7] {
+            for offset in &[0, 1, 63, 64, 65, 1000, u64::MAX - 1, u64::MAX] {
+                ids.push(id(*prefix, *offset));
+            }
+        }
+        ids.extend((0..200).map(|_| ProcessUniqueId::new()));
+
+        let map: IdMap<_> = ids.iter().map(|&id| (id, id.offset())).collect();
+        let expected: BTreeMap<_, _> = ids.iter().map(|&id| (id, id.offset())).collect();
+        assert_eq!(map.len(), expected.len());
+        let iter = map.iter();
+        assert_eq!(iter.len(), expected.len());
+        assert!(iter.eq(expected.iter().map(|(&id, v)| (id, v))));
+    }
+
+    fn ids() -> Vec<ProcessUniqueId> {
+        (0..1000).map(|_| ProcessUniqueId::new()).collect()
+    }
+
+    #[bench]
+    fn bench_insert_btree_map(b: &mut Bencher) {
+        let ids = ids();
+        b.iter(|| ids.iter().map(|&id| (id, ())).collect::<BTreeMap<_, _>>());
+    }
+
+    #[bench]
+    fn bench_insert_id_map(b: &mut Bencher) {
+        let ids = ids();
+        b.iter(|| ids.iter().map(|&id| (id, ())).collect::<IdMap<_>>());
+    }
+
+    #[bench]
+    fn bench_lookup_btree_map(b: &mut Bencher) {
+        let ids = ids();
+        let map: BTreeMap<_, _> = ids.iter().map(|&id| (id, ())).collect();
+        b.iter(|| ids.iter().filter(|id| map.contains_key(id)).count());
+    }
+
+    #[bench]
+    fn bench_lookup_id_map(b: &mut Bencher) {
+        let ids = ids();
+        let map: IdMap<_> = ids.iter().map(|&id| (id, ())).collect();
+        b.iter(|| ids.iter().filter(|id| map.contains_key(id)).count());
+    }
+
+    #[bench]
+    fn bench_iter_btree_map(b: &mut Bencher) {
+        let ids = ids();
+        let map: BTreeMap<_, _> = ids.iter().map(|&id| (id, ())).collect();
+        b.iter(|| map.iter().fold(0, |sum, (id, _)| sum ^ id.offset()));
+    }
+
+    #[bench]
+    fn bench_iter_id_map(b: &mut Bencher) {
+        let ids = ids();
+        let map: IdMap<_> = ids.iter().map(|&id| (id, ())).collect();
+        b.iter(|| map.iter().fold(0, |sum, (id, _)| sum ^ id.offset()));
+    }
+}
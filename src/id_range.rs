@@ -0,0 +1,96 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::iter::FusedIterator;
+
+use crate::process_unique_id::ProcessUniqueId;
+
+/// A block of consecutive unique IDs reserved with `ProcessUniqueId::reserve`.
+///
+/// All IDs in a range share a prefix and have consecutive offsets. The range is an iterator over
+/// the IDs it hasn't yet handed out; `len` and `contains` only consider those.
+///
+/// Ranges are `Send`: reserve a block on one thread and hand it to another.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct IdRange {
+    prefix: usize,
+    start: u64,
+    end: u64,
+}
+
+impl IdRange {
+    #[inline]
+    pub(crate) fn new(prefix: usize, start: u64, end: u64) -> Self {
+        IdRange { prefix, start, end }
+    }
+
+    /// The number of IDs left in this range.
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Returns true if there are no IDs left in this range.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Returns true if `id` is one of the IDs left in this range.
+    #[inline]
+    pub fn contains(&self, id: &ProcessUniqueId) -> bool {
+        id.prefix() == self.prefix && self.start <= id.offset() && id.offset() < self.end
+    }
+}
+
+impl Iterator for IdRange {
+    type Item = ProcessUniqueId;
+
+    #[inline]
+    fn next(&mut self) -> Option<ProcessUniqueId> {
+        if self.is_empty() {
+            return None;
+        }
+        let id = ProcessUniqueId::from_parts(self.prefix, self.start);
+        self.start += 1;
+        Some(id)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        if len <= usize::MAX as u64 {
+            (len as usize, Some(len as usize))
+        } else {
+            (usize::MAX, None)
+        }
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<ProcessUniqueId> {
+        if (n as u64) < self.len() {
+            self.start += n as u64;
+            self.next()
+        } else {
+            self.start = self.end;
+            None
+        }
+    }
+}
+
+impl DoubleEndedIterator for IdRange {
+    #[inline]
+    fn next_back(&mut self) -> Option<ProcessUniqueId> {
+        if self.is_empty() {
+            return None;
+        }
+        self.end -= 1;
+        Some(ProcessUniqueId::from_parts(self.prefix, self.end))
+    }
+}
+
+impl FusedIterator for IdRange {}
@@ -0,0 +1,331 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::cell::UnsafeCell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::thread::LocalKey;
+
+use portable_atomic::AtomicUsize;
+
+use crate::process_unique_id::{advance, parse_untagged, ParseIdError, ProcessUniqueId};
+
+/// An independent namespace of process unique IDs.
+///
+/// Every space has its own prefix counter and its own per-thread offsets so IDs created in one
+/// space never eat into the prefixes available to another (or to `ProcessUniqueId`). Declare
+/// spaces with `id_space!` rather than implementing this trait by hand.
+///
+/// ```
+/// #[macro_use]
+/// extern crate snowflake;
+///
+/// use snowflake::SpaceId;
+///
+/// id_space! {
+///     /// IDs for sessions.
+///     pub struct Sessions;
+/// }
+///
+/// # fn main() {
+/// let id: SpaceId<Sessions> = SpaceId::new();
+/// println!("{}", id); // Sessions-0-0
+/// # }
+/// ```
+pub trait IdSpace: 'static {
+    /// The name of this space, used when displaying its IDs.
+    const NAME: &'static str;
+
+    #[doc(hidden)]
+    fn __local() -> &'static LocalKey<LocalIds>;
+}
+
+/// Declare a new `IdSpace`.
+///
+/// This expands to a unit struct implementing `IdSpace` along with the static counter and thread
+/// local state backing it. Attributes (including doc comments) and a visibility may be given.
+#[macro_export]
+macro_rules! id_space {
+    ($(#[$attr:meta])* $vis:vis struct $name:ident;) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+        $vis struct $name;
+
+        impl $crate::IdSpace for $name {
+            const NAME: &'static str = stringify!($name);
+
+            #[inline]
+            fn __local() -> &'static ::std::thread::LocalKey<$crate::__private::LocalIds> {
+                static COUNTER: $crate::__private::AtomicUsize =
+                    $crate::__private::AtomicUsize::new(0);
+                ::std::thread_local! {
+                    static LOCAL: $crate::__private::LocalIds =
+                        $crate::__private::LocalIds::new(&COUNTER);
+                }
+                &LOCAL
+            }
+        }
+    };
+}
+
+/// The next ID the current thread will hand out in some space.
+#[doc(hidden)]
+pub struct LocalIds {
+    // NOTE: We could use a Cell (not unsafe) but this is slightly faster.
+    next: UnsafeCell<Option<ProcessUniqueId>>,
+    counter: &'static AtomicUsize,
+}
+
+impl LocalIds {
+    pub fn new(counter: &'static AtomicUsize) -> Self {
+        LocalIds {
+            next: UnsafeCell::new(None),
+            counter,
+        }
+    }
+
+    #[inline]
+    fn next(&self) -> ProcessUniqueId {
+        // Safe because `LocalIds` isn't `Sync` and `advance` doesn't call back into it.
+        unsafe { advance(&mut *self.next.get(), self.counter) }
+    }
+}
+
+/// A unique ID from the space `S`.
+///
+/// These are unique within their space for the lifetime of the current process. IDs from
+/// different spaces have different types and can't be compared.
+///
+/// Human readable serialization formats store the `Display` form (`{space}-{prefix}-{offset}`)
+/// and refuse to deserialize an ID from a different space. Binary formats store the same bytes as
+/// `ProcessUniqueId` and don't record the space.
+pub struct SpaceId<S: IdSpace> {
+    id: ProcessUniqueId,
+    _space: PhantomData<fn() -> S>,
+}
+
+impl<S: IdSpace> SpaceId<S> {
+    /// Create a new unique ID in the space `S`.
+    ///
+    /// **panics** if `S` runs out of unique IDs (see `ProcessUniqueId` for the limits).
+    #[inline]
+    pub fn new() -> Self {
+        SpaceId {
+            id: S::__local().with(LocalIds::next),
+            _space: PhantomData,
+        }
+    }
+}
+
+impl<S: IdSpace> Default for SpaceId<S> {
+    #[inline]
+    fn default() -> Self {
+        SpaceId::new()
+    }
+}
+
+impl<S: IdSpace> Clone for SpaceId<S> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S: IdSpace> Copy for SpaceId<S> {}
+
+impl<S: IdSpace> PartialEq for SpaceId<S> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<S: IdSpace> Eq for SpaceId<S> {}
+
+impl<S: IdSpace> PartialOrd for SpaceId<S> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S: IdSpace> Ord for SpaceId<S> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+impl<S: IdSpace> Hash for SpaceId<S> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state)
+    }
+}
+
+impl<S: IdSpace> fmt::Debug for SpaceId<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SpaceId")
+            .field("space", &S::NAME)
+            .field("prefix", &self.id.prefix())
+            .field("offset", &self.id.offset())
+            .finish()
+    }
+}
+
+impl<S: IdSpace> fmt::Display for SpaceId<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}-{:x}-{:x}",
+            S::NAME,
+            self.id.prefix(),
+            self.id.offset()
+        )
+    }
+}
+
+impl<S: IdSpace> FromStr for SpaceId<S> {
+    type Err = ParseIdError;
+
+    /// Parse an ID in the `Display` form, checking that it belongs to the space `S`.
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        let tag = S::NAME.len();
+        if !s.starts_with(S::NAME) || s.as_bytes().get(tag) != Some(&b'-') {
+            return Err(ParseIdError::MissingTag);
+        }
+        parse_untagged(&s[tag + 1..]).map(|id| SpaceId {
+            id,
+            _space: PhantomData,
+        })
+    }
+}
+
+#[cfg(feature = "serde_support")]
+impl<S: IdSpace> serde::Serialize for SpaceId<S> {
+    fn serialize<Ser: serde::Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            self.id.serialize(serializer)
+        }
+    }
+}
+
+#[cfg(feature = "serde_support")]
+impl<'de, S: IdSpace> serde::Deserialize<'de> for SpaceId<S> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(SpaceIdVisitor(PhantomData))
+        } else {
+            ProcessUniqueId::deserialize(deserializer).map(|id| SpaceId {
+                id,
+                _space: PhantomData,
+            })
+        }
+    }
+}
+
+#[cfg(feature = "serde_support")]
+struct SpaceIdVisitor<S>(PhantomData<fn() -> S>);
+
+#[cfg(feature = "serde_support")]
+impl<'de, S: IdSpace> serde::de::Visitor<'de> for SpaceIdVisitor<S> {
+    type Value = SpaceId<S>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an ID from the space {}", S::NAME)
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<SpaceId<S>, E> {
+        v.parse().map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SpaceId;
+    use crate::ParseIdError;
+    use std::thread;
+
+    // Only `test_independent_spaces` creates IDs in `Red` and `Blue` so it knows which prefixes
+    // they hand out. The other tests create theirs in `Green`.
+    id_space! {
+        struct Red;
+    }
+
+    id_space! {
+        pub(crate) struct Blue;
+    }
+
+    id_space! {
+        struct Green;
+    }
+
+    #[test]
+    fn test_independent_spaces() {
+        // Each space hands out its own prefixes, starting from zero.
+        let red: SpaceId<Red> = SpaceId::new();
+        let blue: SpaceId<Blue> = SpaceId::new();
+        assert_eq!(red.to_string(), "Red-0-0");
+        assert_eq!(blue.to_string(), "Blue-0-0");
+        assert_eq!(SpaceId::<Red>::new().to_string(), "Red-0-1");
+
+        let other = thread::spawn(|| SpaceId::<Red>::new().to_string());
+        assert_eq!(other.join().unwrap(), "Red-1-0");
+        assert_eq!(SpaceId::<Blue>::new().to_string(), "Blue-0-1");
+    }
+
+    #[test]
+    fn test_parse() {
+        let green: SpaceId<Green> = SpaceId::new();
+        assert_eq!(green.to_string().parse::<SpaceId<Green>>(), Ok(green));
+        assert_eq!(
+            green.to_string().parse::<SpaceId<Blue>>(),
+            Err(ParseIdError::MissingTag)
+        );
+        assert_eq!(
+            "Greenn-0-0".parse::<SpaceId<Green>>(),
+            Err(ParseIdError::MissingTag)
+        );
+        assert_eq!(
+            "puid-0-0".parse::<SpaceId<Green>>(),
+            Err(ParseIdError::MissingTag)
+        );
+        assert_eq!(
+            "Green-01-0".parse::<SpaceId<Green>>(),
+            Err(ParseIdError::InvalidPrefix)
+        );
+    }
+
+    #[cfg(feature = "serde_support")]
+    #[test]
+    fn test_serde() {
+        extern crate bincode;
+        extern crate serde_json;
+        use crate::ProcessUniqueId;
+
+        let green: SpaceId<Green> = SpaceId::new();
+        let json = serde_json::to_string(&green).unwrap();
+        assert_eq!(json, format!("\"{}\"", green));
+        assert_eq!(
+            serde_json::from_str::<SpaceId<Green>>(&json).unwrap(),
+            green
+        );
+        assert!(serde_json::from_str::<SpaceId<Blue>>(&json).is_err());
+        assert!(serde_json::from_str::<ProcessUniqueId>(&json).is_err());
+
+        let bytes = bincode::serialize(&green).unwrap();
+        assert_eq!(
+            bincode::deserialize::<SpaceId<Green>>(&bytes).unwrap(),
+            green
+        );
+    }
+}
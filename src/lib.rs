@@ -7,15 +7,117 @@
 // except according to those terms.
 
 #![cfg_attr(test, feature(test))]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 //! A crate for quickly generating unique IDs with guaranteed properties.
 //!
-//! This crate currently includes guaranteed process unique IDs but may include new ID types in the
-//! future.
+//! This crate currently includes:
+//!
+//! * `ProcessUniqueId`: guaranteed process unique IDs, and `Id<T>`, the same IDs tagged with the
+//!   type of the thing they identify. `DeterministicGenerator` produces the same IDs on every run
+//!   for tests and simulations.
+//! * `CompactProcessUniqueId`: process unique IDs that fit in a `u64`.
+//! * `PersistentUniqueId`: process unique IDs tagged with a random per-process nonce so they stay
+//!   unique when persisted and read back in a later run.
+//! * `HostUniqueId`: IDs unique across every process on a host that shares a counter file.
+//! * `SequentialId`: process unique IDs that sort in creation order across threads.
+//! * `SpaceId<S>`: process unique IDs drawn from an independent namespace declared with
+//!   `id_space!`.
+//! * `SnowflakeId`: time ordered IDs that are unique across machines given distinct worker IDs.
+//! * `Ulid`: sortable 128 bit IDs that are unique across machines thanks to 80 random bits.
+//! * `UuidV7`: RFC 9562 version 7 UUIDs, generated without touching the OS random source on every
+//!   call.
+//! * `GenerationalId`: slot indices tagged with a generation, for slot arrays that reuse slots.
+//!
+//! `IdHashMap` and `IdHashSet` are hash collections with a fast hasher for `ProcessUniqueId` keys
+//! and `IdMap` is an ordered map that stores runs of consecutive IDs densely.
+//! `AtomicProcessUniqueId` and friends hold IDs that are shared between threads.
+//!
+//! # Features
+//!
+//! * `std` (default): use thread locals for fast ID creation and enable the ID types that need the
+//!   standard library (clocks, thread locals, or OS randomness). Without it, this crate only needs
+//!   `core` and `ProcessUniqueId`s are created from atomics alone.
+//! * `critical-section`: on targets without compare-and-swap, implement atomics with the
+//!   `critical-section` crate.
+//! * `host` (unix only): enable `HostUniqueId`.
+//! * `serde_support`: implement `Serialize` and `Deserialize`.
+//! * `testing`: add the `testing` module for choosing the IDs `ProcessUniqueId::new` returns in
+//!   tests. Don't enable this outside of `dev-dependencies`.
+//! * `uuid`: convert to and from `uuid::Uuid`. `ProcessUniqueId`s map losslessly to and from
+//!   version 8 UUIDs.
 
 #[cfg(feature = "serde_support")]
 #[macro_use]
 extern crate serde_derive;
 
+mod atomic_id;
+mod compact_id;
+mod deterministic;
+#[cfg(feature = "std")]
+mod free_list;
+mod generational_id;
+mod hasher;
+#[cfg(all(feature = "host", unix))]
+mod host_id;
+#[cfg(feature = "std")]
+mod id_generator;
+#[cfg(feature = "std")]
+pub mod id_map;
+mod id_range;
+#[cfg(feature = "std")]
+mod id_space;
+#[cfg(feature = "std")]
+mod persistent_id;
 mod process_unique_id;
+#[cfg(feature = "std")]
+mod sequential_id;
+#[cfg(feature = "std")]
+mod snowflake_id;
+mod splitmix;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod typed_id;
+mod ulid;
+mod uuid_v7;
+#[cfg(feature = "uuid")]
+mod uuid_v8;
+
+pub use crate::atomic_id::{
+    AtomicCompactProcessUniqueId, AtomicOptionProcessUniqueId, AtomicProcessUniqueId,
+};
+pub use crate::compact_id::CompactProcessUniqueId;
+pub use crate::deterministic::DeterministicGenerator;
+#[cfg(feature = "std")]
+pub use crate::generational_id::GenerationalIdAllocator;
+pub use crate::generational_id::{GenerationalId, ParseGenerationalIdError};
+pub use crate::hasher::{BuildIdHasher, ProcessUniqueIdHasher};
+#[cfg(feature = "std")]
+pub use crate::hasher::{IdHashMap, IdHashSet};
+#[cfg(all(feature = "host", unix))]
+pub use crate::host_id::HostUniqueId;
+#[cfg(feature = "std")]
+pub use crate::id_generator::IdGenerator;
+#[cfg(feature = "std")]
+pub use crate::id_map::IdMap;
+pub use crate::id_range::IdRange;
+#[cfg(feature = "std")]
+pub use crate::id_space::{IdSpace, SpaceId};
+#[cfg(feature = "std")]
+pub use crate::persistent_id::PersistentUniqueId;
+pub use crate::process_unique_id::{ExhaustedError, ParseIdError, ProcessUniqueId};
+#[cfg(feature = "std")]
+pub use crate::sequential_id::SequentialId;
+#[cfg(feature = "std")]
+pub use crate::snowflake_id::{SnowflakeGenerator, SnowflakeId, MAX_WORKER_ID};
+pub use crate::typed_id::Id;
+#[cfg(feature = "std")]
+pub use crate::ulid::UlidGenerator;
+pub use crate::ulid::{ParseUlidError, Ulid};
+pub use crate::uuid_v7::{UuidConversionError, UuidV7};
 
-pub use crate::process_unique_id::ProcessUniqueId;
+#[doc(hidden)]
+#[cfg(feature = "std")]
+pub mod __private {
+    pub use crate::id_space::LocalIds;
+    pub use portable_atomic::AtomicUsize;
+}
@@ -0,0 +1,116 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::process_unique_id::ProcessUniqueId;
+
+static INSTANCE: OnceLock<u64> = OnceLock::new();
+
+#[inline]
+fn instance() -> u64 {
+    *INSTANCE.get_or_init(|| {
+        getrandom::u64().expect("failed to read a process instance nonce from the OS random source")
+    })
+}
+
+/// A `ProcessUniqueId` that stays unique across process restarts.
+///
+/// `ProcessUniqueId`s start over every time the process starts so persisting them and reading
+/// them back in a later run risks duplicates. `PersistentUniqueId`s add a 64 bit nonce, read from
+/// the OS random source once per process, to tell runs apart.
+///
+/// Creating one costs the same as creating a `ProcessUniqueId` (plus an atomic load). IDs from
+/// the same run are unique for sure; IDs from different runs only collide if both runs happened
+/// to draw the same nonce (a one in 2^64 chance for any given pair of runs).
+///
+/// IDs from the same run sort in the same order as their `ProcessUniqueId`s but IDs from
+/// different runs are ordered by nonce, not by run.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct PersistentUniqueId {
+    instance: u64,
+    id: ProcessUniqueId,
+}
+
+impl PersistentUniqueId {
+    /// Create a new unique ID.
+    ///
+    /// **panics** if the OS random source can't be read (the first time this is called) or if
+    /// `ProcessUniqueId::new` would.
+    #[inline]
+    pub fn new() -> Self {
+        PersistentUniqueId {
+            instance: instance(),
+            id: ProcessUniqueId::new(),
+        }
+    }
+
+    /// The nonce identifying the process that created this ID.
+    #[inline]
+    pub fn instance(self) -> u64 {
+        self.instance
+    }
+
+    /// The process unique part of this ID.
+    #[inline]
+    pub fn process_unique_id(self) -> ProcessUniqueId {
+        self.id
+    }
+
+    /// Returns true if this ID was created by the current process.
+    #[inline]
+    pub fn is_from_current_process(self) -> bool {
+        self.instance == instance()
+    }
+}
+
+impl Default for PersistentUniqueId {
+    #[inline]
+    fn default() -> Self {
+        PersistentUniqueId::new()
+    }
+}
+
+impl fmt::Display for PersistentUniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ppuid-{:016x}-{:x}-{:x}",
+            self.instance,
+            self.id.prefix(),
+            self.id.offset()
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PersistentUniqueId;
+    use std::thread;
+
+    #[test]
+    fn test_same_instance() {
+        let a = PersistentUniqueId::new();
+        let b = thread::spawn(PersistentUniqueId::new).join().unwrap();
+        assert_ne!(a, b);
+        assert_eq!(a.instance(), b.instance());
+        assert!(a.is_from_current_process());
+        assert_ne!(a.process_unique_id(), b.process_unique_id());
+    }
+
+    #[test]
+    fn test_display() {
+        let id = PersistentUniqueId::new();
+        let puid = id.process_unique_id().to_string();
+        assert_eq!(
+            id.to_string(),
+            format!("ppuid-{:016x}-{}", id.instance(), &puid["puid-".len()..])
+        );
+    }
+}
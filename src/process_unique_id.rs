@@ -5,37 +5,178 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
-use std::cell::UnsafeCell;
+#[cfg(feature = "std")]
+use core::cell::UnsafeCell;
 
-use std::default::Default;
-use std::fmt;
-use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
-use std::{u64, usize};
+use core::convert::TryFrom;
+use core::default::Default;
+use core::fmt;
+use core::num::NonZeroUsize;
+use core::str::FromStr;
 
-static GLOBAL_COUNTER: AtomicUsize = ATOMIC_USIZE_INIT;
+#[cfg(feature = "std")]
+use crate::id_generator::IdGenerator;
+use crate::id_range::IdRange;
+#[cfg(not(feature = "std"))]
+use portable_atomic::AtomicU64;
+use portable_atomic::{AtomicUsize, Ordering};
 
+pub(crate) static GLOBAL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(any(test, not(feature = "std")))]
 fn next_global() -> usize {
-    let mut prev = GLOBAL_COUNTER.load(Ordering::Relaxed);
+    next_prefix(&GLOBAL_COUNTER)
+}
+
+fn try_next_global() -> Result<usize, ExhaustedError> {
+    try_next_prefix(&GLOBAL_COUNTER)
+}
+
+/// Reserve a fresh prefix from `counter`.
+///
+/// **panics** if `counter` has run out of prefixes.
+pub(crate) fn next_prefix(counter: &AtomicUsize) -> usize {
+    try_next_prefix(counter).unwrap_or_else(|_| exhausted())
+}
+
+/// Reserve a fresh prefix from `counter`, failing if it has run out.
+pub(crate) fn try_next_prefix(counter: &AtomicUsize) -> Result<usize, ExhaustedError> {
+    let mut prev = counter.load(Ordering::Relaxed);
     loop {
-        assert!(
-            prev < usize::MAX,
-            "Snow Crash: Go home and reevaluate your threading model!"
-        );
-        let old_value = GLOBAL_COUNTER.compare_and_swap(prev, prev + 1, Ordering::Relaxed);
-        if old_value == prev {
-            return prev;
-        } else {
-            prev = old_value;
+        if prev == usize::MAX {
+            return Err(ExhaustedError(()));
+        }
+        match counter.compare_exchange_weak(prev, prev + 1, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => return Ok(prev),
+            Err(old_value) => prev = old_value,
         }
     }
 }
 
+#[cold]
+pub(crate) fn exhausted() -> ! {
+    panic!("Snow Crash: Go home and reevaluate your threading model!")
+}
+
+/// An error returned when there are no more unique IDs available.
+///
+/// This only happens after an absurd number of threads have created IDs (see the limits on
+/// `ProcessUniqueId`).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ExhaustedError(pub(crate) ());
+
+impl fmt::Display for ExhaustedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("no more unique IDs available")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ExhaustedError {}
+
+/// A prefix shared by every thread, reserved through `next_global` on first use.
+pub(crate) struct LazyPrefix(AtomicUsize);
+
+impl LazyPrefix {
+    // `next_global` never hands out `usize::MAX` so we can use it to mean "not reserved yet".
+    pub(crate) const fn new() -> Self {
+        LazyPrefix(AtomicUsize::new(usize::MAX))
+    }
+
+    /// **panics** if the prefix hasn't been reserved yet and there are none left.
+    #[inline]
+    pub(crate) fn get(&self) -> usize {
+        self.try_get().unwrap_or_else(|_| exhausted())
+    }
+
+    #[inline]
+    pub(crate) fn try_get(&self) -> Result<usize, ExhaustedError> {
+        let prefix = self.0.load(Ordering::Acquire);
+        if prefix != usize::MAX {
+            return Ok(prefix);
+        }
+        // If we lose the race, the prefix we reserved is simply never used.
+        let fresh = try_next_global()?;
+        match self
+            .0
+            .compare_exchange(usize::MAX, fresh, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => Ok(fresh),
+            Err(prefix) => Ok(prefix),
+        }
+    }
+}
+
+// Without thread locals, every thread shares a single prefix and bumps a global offset.
+#[cfg(not(feature = "std"))]
+static SHARED_PREFIX: LazyPrefix = LazyPrefix::new();
+#[cfg(not(feature = "std"))]
+static SHARED_OFFSET: AtomicU64 = AtomicU64::new(0);
+
 // NOTE: We could use a Cell (not unsafe) but this is slightly faster.
+#[cfg(feature = "std")]
 thread_local! {
-    static NEXT_LOCAL_UNIQUE_ID: UnsafeCell<ProcessUniqueId> = UnsafeCell::new(ProcessUniqueId {
-        prefix: next_global(),
-        offset: 0
-    })
+    static NEXT_LOCAL_UNIQUE_ID: UnsafeCell<IdGenerator> = const {
+        UnsafeCell::new(IdGenerator::new())
+    }
+}
+
+/// Hand out `next` and advance it, moving on to a fresh prefix from `counter` once the offsets
+/// under the current prefix run out.
+///
+/// **panics** if `counter` runs out of prefixes.
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn advance(
+    next: &mut Option<ProcessUniqueId>,
+    counter: &AtomicUsize,
+) -> ProcessUniqueId {
+    try_advance(next, counter).unwrap_or_else(|_| exhausted())
+}
+
+/// Like `advance` but fails instead of panicking. On failure, `next` is left untouched.
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn try_advance(
+    next: &mut Option<ProcessUniqueId>,
+    counter: &AtomicUsize,
+) -> Result<ProcessUniqueId, ExhaustedError> {
+    let next_unique_id = match *next {
+        Some(id) => id,
+        None => ProcessUniqueId::from_parts(try_next_prefix(counter)?, 0),
+    };
+    // NOTE: Checked ops are slower than manually checking... (WTF?)
+    *next = if next_unique_id.offset == u64::MAX {
+        None
+    } else {
+        Some(ProcessUniqueId {
+            prefix: next_unique_id.prefix,
+            offset: next_unique_id.offset + 1,
+        })
+    };
+    Ok(next_unique_id)
+}
+
+/// Reserve `n` consecutive IDs, taking them from `next` if they fit under its prefix and from a
+/// fresh prefix from `counter` otherwise.
+///
+/// **panics** if `counter` runs out of prefixes.
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn reserve_from(
+    next: &mut Option<ProcessUniqueId>,
+    n: u64,
+    counter: &AtomicUsize,
+) -> IdRange {
+    let next = next.get_or_insert_with(|| ProcessUniqueId::from_parts(next_prefix(counter), 0));
+    // The offsets from `next.offset` through `u64::MAX` are still available.
+    if n <= u64::MAX - next.offset {
+        let start = next.offset;
+        next.offset += n;
+        IdRange::new(next.prefix(), start, start + n)
+    } else {
+        IdRange::new(next_prefix(counter), 0, n)
+    }
 }
 
 /// Process unique IDs are guaranteed to be unique within the current process, for the lifetime of
@@ -54,45 +195,295 @@ thread_local! {
 /// IDs in a reasonable amount of time is to run a 32bit system, spawn 2^32 threads, and claim one
 /// ID on each thread. You might be able to do this on a 64bit system but it would take a while...
 /// TL; DR: Don't create unique IDs from over 4 billion different threads on a 32bit system.
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
-#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+///
+/// That said, when a thread exits, the rest of its prefix is handed to the next thread that needs
+/// one (if there's room in a small pool of returned prefixes), so threads that come and go don't
+/// count against this limit nearly as much as threads that are alive at the same time.
+///
+/// The prefix is never `usize::MAX`. This leaves room for a niche, so `Option<ProcessUniqueId>`
+/// is the same size as `ProcessUniqueId`.
+///
+/// # Serialization
+///
+/// With the `serde_support` feature, IDs serialize as their `Display` string in human readable
+/// formats (JSON, TOML, etc.) and as a `(u64, u64)` tuple of prefix and offset in binary formats,
+/// so they can be read back on platforms with a different `usize`. Deserialization also accepts
+/// the `{prefix, offset}` struct written by older versions of this crate.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ProcessUniqueId {
-    prefix: usize,
+    // The prefix plus one, so `Option<ProcessUniqueId>` can use 0 to mean `None`. Prefixes never
+    // reach `usize::MAX` (see `try_next_prefix`) so this doesn't overflow.
+    prefix: NonZeroUsize,
     offset: u64,
 }
 
+impl fmt::Debug for ProcessUniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProcessUniqueId")
+            .field("prefix", &self.prefix())
+            .field("offset", &self.offset)
+            .finish()
+    }
+}
+
 impl fmt::Display for ProcessUniqueId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "puid-{:x}-{:x}", self.prefix, self.offset)
+        write!(f, "puid-{:x}-{:x}", self.prefix(), self.offset)
+    }
+}
+
+/// An error returned when parsing a `ProcessUniqueId` (or a `SpaceId`) from a string fails.
+///
+/// The only accepted form is the one produced by `Display`: `puid-{prefix}-{offset}` where both
+/// numbers are lowercase hexadecimal without leading zeros. `SpaceId`s use the name of their space
+/// in place of `puid`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ParseIdError {
+    /// The string doesn't start with `puid-` (or the name of the space followed by `-`).
+    MissingTag,
+    /// There is no `-` between the prefix and the offset.
+    MissingSeparator,
+    /// The prefix isn't canonical lowercase hex.
+    InvalidPrefix,
+    /// The offset isn't canonical lowercase hex.
+    InvalidOffset,
+    /// The prefix is `usize::MAX` or larger on this platform.
+    PrefixOverflow,
+    /// The offset doesn't fit in a `u64`.
+    OffsetOverflow,
+}
+
+impl fmt::Display for ParseIdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ParseIdError::MissingTag => "process unique ID is missing its tag",
+            ParseIdError::MissingSeparator => "process unique ID is missing the offset",
+            ParseIdError::InvalidPrefix => "invalid process unique ID prefix",
+            ParseIdError::InvalidOffset => "invalid process unique ID offset",
+            ParseIdError::PrefixOverflow => {
+                "process unique ID prefix is too large for this platform"
+            }
+            ParseIdError::OffsetOverflow => "process unique ID offset is too large",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseIdError {}
+
+pub(crate) enum HexError {
+    Invalid,
+    Overflow,
+}
+
+/// Parse a canonical hex number: lowercase digits, no sign, and no leading zeros (so there is
+/// exactly one string per value).
+pub(crate) fn parse_hex(s: &str) -> Result<u64, HexError> {
+    let bytes = s.as_bytes();
+    if bytes.is_empty() || (bytes[0] == b'0' && bytes.len() > 1) {
+        return Err(HexError::Invalid);
+    }
+    if !bytes.iter().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return Err(HexError::Invalid);
+    }
+    // Without leading zeros, anything longer than 16 digits can't fit.
+    if bytes.len() > 16 {
+        return Err(HexError::Overflow);
+    }
+    Ok(bytes.iter().fold(0, |value, &b| {
+        let digit = match b {
+            b'0'..=b'9' => b - b'0',
+            _ => b - b'a' + 10,
+        };
+        (value << 4) | u64::from(digit)
+    }))
+}
+
+impl FromStr for ProcessUniqueId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        if !s.starts_with("puid-") {
+            return Err(ParseIdError::MissingTag);
+        }
+        parse_untagged(&s[5..])
+    }
+}
+
+/// Parse the `{prefix}-{offset}` that follows the tag of a process unique ID.
+pub(crate) fn parse_untagged(rest: &str) -> Result<ProcessUniqueId, ParseIdError> {
+    let sep = rest.find('-').ok_or(ParseIdError::MissingSeparator)?;
+    let (prefix, offset) = (&rest[..sep], &rest[sep + 1..]);
+
+    let prefix = match parse_hex(prefix) {
+        Ok(prefix) => prefix,
+        Err(HexError::Invalid) => return Err(ParseIdError::InvalidPrefix),
+        Err(HexError::Overflow) => return Err(ParseIdError::PrefixOverflow),
+    };
+    let offset = match parse_hex(offset) {
+        Ok(offset) => offset,
+        Err(HexError::Invalid) => return Err(ParseIdError::InvalidOffset),
+        Err(HexError::Overflow) => return Err(ParseIdError::OffsetOverflow),
+    };
+    ProcessUniqueId::try_from_parts(prefix, offset).ok_or(ParseIdError::PrefixOverflow)
+}
+
+impl<'a> TryFrom<&'a str> for ProcessUniqueId {
+    type Error = ParseIdError;
+
+    #[inline]
+    fn try_from(s: &'a str) -> Result<Self, ParseIdError> {
+        s.parse()
     }
 }
 
 impl ProcessUniqueId {
     /// Create a new unique ID.
     ///
+    /// Without the `std` feature there are no thread locals to keep per-thread offsets in, so
+    /// every thread takes offsets from a single atomic counter instead. This is slower under
+    /// contention but otherwise has the same guarantees.
+    ///
+    /// This can be called while the current thread's thread locals are being destroyed (e.g. from
+    /// another thread local's destructor). It's slower then as every call has to claim a prefix
+    /// of its own; keep an `IdGenerator` around if you create lots of IDs at that point.
+    ///
     /// **panics** if there are no more unique IDs available. If this happens, go home and
     /// reevaluate your threading model!
     #[inline]
     pub fn new() -> Self {
-        NEXT_LOCAL_UNIQUE_ID.with(|unique_id| {
-            unsafe {
-                // NOTE: Checked ops are slower than manually checking... (WTF?)
-                let next_unique_id = *unique_id.get();
-                (*unique_id.get()) = if next_unique_id.offset == u64::MAX {
-                    ProcessUniqueId {
-                        prefix: next_global(),
-                        offset: 0,
-                    }
-                } else {
-                    ProcessUniqueId {
-                        prefix: next_unique_id.prefix,
-                        offset: next_unique_id.offset + 1,
-                    }
-                };
-                next_unique_id
+        ProcessUniqueId::try_new().unwrap_or_else(|_| exhausted())
+    }
+
+    /// Create a new unique ID, failing instead of panicking if there are no more unique IDs
+    /// available.
+    #[inline]
+    pub fn try_new() -> Result<Self, ExhaustedError> {
+        #[cfg(feature = "testing")]
+        {
+            if let Some(id) = crate::testing::next_override() {
+                return Ok(id);
             }
+        }
+        #[cfg(feature = "std")]
+        {
+            // Safe because `IdGenerator` doesn't call back into `NEXT_LOCAL_UNIQUE_ID`. While
+            // thread locals are being destroyed, use (and give back) a prefix of our own.
+            NEXT_LOCAL_UNIQUE_ID
+                .try_with(|gen| unsafe { (*gen.get()).try_next_id() })
+                .unwrap_or_else(|_| IdGenerator::new().try_next_id())
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            let prefix = SHARED_PREFIX.try_get()?;
+            let offset = SHARED_OFFSET
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |offset| {
+                    offset.checked_add(1)
+                })
+                .map_err(|_| ExhaustedError(()))?;
+            Ok(ProcessUniqueId::from_parts(prefix, offset))
+        }
+    }
+
+    /// Reserve `n` consecutive unique IDs at once.
+    ///
+    /// This is much faster than calling `new()` `n` times. The IDs come from the current thread's
+    /// prefix when it has room for them (`new()` then carries on after the range) and from a
+    /// freshly reserved prefix otherwise.
+    ///
+    /// **panics** if there are no more unique IDs available.
+    #[inline]
+    pub fn reserve(n: u64) -> IdRange {
+        #[cfg(feature = "std")]
+        {
+            NEXT_LOCAL_UNIQUE_ID
+                .try_with(|gen| unsafe { (*gen.get()).reserve(n) })
+                .unwrap_or_else(|_| IdGenerator::new().reserve(n))
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            match SHARED_OFFSET.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |offset| {
+                offset.checked_add(n)
+            }) {
+                Ok(start) => IdRange::new(SHARED_PREFIX.get(), start, start + n),
+                Err(_) => IdRange::new(next_global(), 0, n),
+            }
+        }
+    }
+
+    /// Encode this ID as 16 bytes: the prefix, widened to 64 bits, followed by the offset, both
+    /// big endian.
+    ///
+    /// The encoding is the same on every platform and byte strings compare in the same order as
+    /// the IDs they encode, so they make good keys in ordered key-value stores.
+    #[inline]
+    pub fn to_bytes(self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&(self.prefix() as u64).to_be_bytes());
+        bytes[8..].copy_from_slice(&self.offset.to_be_bytes());
+        bytes
+    }
+
+    /// Decode an ID encoded with `to_bytes`.
+    ///
+    /// Fails with `ParseIdError::PrefixOverflow` if the prefix is `usize::MAX` or larger on this
+    /// platform.
+    #[inline]
+    pub fn from_bytes(bytes: [u8; 16]) -> Result<Self, ParseIdError> {
+        let mut prefix = [0; 8];
+        let mut offset = [0; 8];
+        prefix.copy_from_slice(&bytes[..8]);
+        offset.copy_from_slice(&bytes[8..]);
+        ProcessUniqueId::try_from_parts(u64::from_be_bytes(prefix), u64::from_be_bytes(offset))
+            .ok_or(ParseIdError::PrefixOverflow)
+    }
+
+    /// **panics** if `prefix` is `usize::MAX`.
+    #[inline]
+    pub(crate) fn from_parts(prefix: usize, offset: u64) -> Self {
+        ProcessUniqueId {
+            prefix: NonZeroUsize::new(prefix.wrapping_add(1)).expect("invalid prefix"),
+            offset,
+        }
+    }
+
+    /// Returns `None` if `prefix` is `usize::MAX` or doesn't fit in a `usize` at all.
+    #[inline]
+    pub(crate) fn try_from_parts(prefix: u64, offset: u64) -> Option<Self> {
+        let prefix = usize::try_from(prefix).ok()?;
+        Some(ProcessUniqueId {
+            prefix: NonZeroUsize::new(prefix.wrapping_add(1))?,
+            offset,
         })
     }
+
+    /// Pack this ID into a `u128` (the stored prefix in the high half) that is never 0.
+    #[inline]
+    pub(crate) const fn to_u128(self) -> u128 {
+        (self.prefix.get() as u128) << 64 | self.offset as u128
+    }
+
+    /// Unpack a value from `to_u128`, mapping 0 to `None`.
+    #[inline]
+    pub(crate) const fn from_u128(value: u128) -> Option<Self> {
+        match NonZeroUsize::new((value >> 64) as usize) {
+            Some(prefix) => Some(ProcessUniqueId {
+                prefix,
+                offset: value as u64,
+            }),
+            None => None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn prefix(self) -> usize {
+        self.prefix.get() - 1
+    }
+
+    #[inline]
+    pub(crate) fn offset(self) -> u64 {
+        self.offset
+    }
 }
 
 impl Default for ProcessUniqueId {
@@ -102,6 +493,129 @@ impl Default for ProcessUniqueId {
     }
 }
 
+#[cfg(feature = "serde_support")]
+mod serde_impl {
+    use core::fmt;
+    use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Unexpected, Visitor};
+    use serde::ser::{Serialize, Serializer};
+
+    use super::ProcessUniqueId;
+
+    impl Serialize for ProcessUniqueId {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if serializer.is_human_readable() {
+                serializer.collect_str(self)
+            } else {
+                (self.prefix() as u64, self.offset).serialize(serializer)
+            }
+        }
+    }
+
+    impl<'de> Deserialize<'de> for ProcessUniqueId {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            if deserializer.is_human_readable() {
+                deserializer.deserialize_any(IdVisitor)
+            } else {
+                deserializer.deserialize_tuple(2, IdVisitor)
+            }
+        }
+    }
+
+    struct IdVisitor;
+
+    impl IdVisitor {
+        fn build<E: de::Error>(self, prefix: u64, offset: u64) -> Result<ProcessUniqueId, E> {
+            ProcessUniqueId::try_from_parts(prefix, offset).ok_or_else(|| {
+                E::invalid_value(
+                    Unexpected::Unsigned(prefix),
+                    &"a prefix less than usize::MAX",
+                )
+            })
+        }
+    }
+
+    impl<'de> Visitor<'de> for IdVisitor {
+        type Value = ProcessUniqueId;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a process unique ID")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<ProcessUniqueId, E> {
+            v.parse().map_err(E::custom)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ProcessUniqueId, A::Error> {
+            let prefix = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            let offset = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+            self.build(prefix, offset)
+        }
+
+        // The struct representation used before serialization was hand written.
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ProcessUniqueId, A::Error> {
+            let (mut prefix, mut offset) = (None, None);
+            while let Some(field) = map.next_key()? {
+                match field {
+                    Field::Prefix if prefix.is_some() => {
+                        return Err(de::Error::duplicate_field("prefix"))
+                    }
+                    Field::Offset if offset.is_some() => {
+                        return Err(de::Error::duplicate_field("offset"))
+                    }
+                    Field::Prefix => prefix = Some(map.next_value()?),
+                    Field::Offset => offset = Some(map.next_value()?),
+                }
+            }
+            let prefix = prefix.ok_or_else(|| de::Error::missing_field("prefix"))?;
+            let offset = offset.ok_or_else(|| de::Error::missing_field("offset"))?;
+            self.build(prefix, offset)
+        }
+    }
+
+    const FIELDS: &[&str] = &["prefix", "offset"];
+
+    enum Field {
+        Prefix,
+        Offset,
+    }
+
+    impl<'de> Deserialize<'de> for Field {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_identifier(FieldVisitor)
+        }
+    }
+
+    struct FieldVisitor;
+
+    impl<'de> Visitor<'de> for FieldVisitor {
+        type Value = Field;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("`prefix` or `offset`")
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Field, E> {
+            match v {
+                0 => Ok(Field::Prefix),
+                1 => Ok(Field::Offset),
+                _ => Err(E::invalid_value(Unexpected::Unsigned(v), &self)),
+            }
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Field, E> {
+            match v {
+                "prefix" => Ok(Field::Prefix),
+                "offset" => Ok(Field::Offset),
+                _ => Err(E::unknown_field(v, FIELDS)),
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     extern crate rand;
@@ -111,41 +625,56 @@ mod test {
     extern crate uuid;
     use self::test::Bencher;
     use self::threadpool::ThreadPool;
-    use super::{next_global, ProcessUniqueId};
+    #[cfg(feature = "std")]
+    use super::try_advance;
+    use super::{next_global, try_next_prefix, ExhaustedError, ParseIdError, ProcessUniqueId};
+    #[cfg(feature = "std")]
+    use crate::id_range::IdRange;
+    #[cfg(feature = "std")]
+    use crate::sequential_id::SequentialId;
+    #[cfg(feature = "std")]
+    use crate::uuid_v7::UuidV7;
     use std::sync::mpsc::channel;
     use std::thread;
-    use std::u64;
 
     // Glass box tests.
 
+    /// Replace the next ID the current thread hands out. The thread's current prefix is dropped,
+    /// not given back, so nearly used up prefixes set here don't leak into other tests.
+    #[cfg(feature = "std")]
+    fn set_local_next(next: Option<ProcessUniqueId>) {
+        use super::NEXT_LOCAL_UNIQUE_ID;
+        NEXT_LOCAL_UNIQUE_ID.with(|gen| unsafe { (*gen.get()).set_next(next) });
+    }
+
+    #[cfg(feature = "std")]
+    fn fresh_prefix() -> usize {
+        super::next_prefix(&super::GLOBAL_COUNTER)
+    }
+
+    #[cfg(feature = "std")]
     #[test]
     fn test_unique_id_unthreaded() {
-        let first_unique_id = ProcessUniqueId::new();
         // Not going to be able to count to u64::MAX
-        {
-            // Ignore....
-            use super::NEXT_LOCAL_UNIQUE_ID;
-            NEXT_LOCAL_UNIQUE_ID
-                .with(|unique_id| unsafe { (*unique_id.get()).offset = u64::MAX - 10 });
-        } // Ignore...
-
-        for i in (u64::MAX - 11)..(u64::MAX) {
-            assert!(
-                ProcessUniqueId::new() == ProcessUniqueId {
-                    prefix: first_unique_id.prefix,
-                    offset: i + 1,
-                }
+        let prefix = fresh_prefix();
+        set_local_next(Some(ProcessUniqueId::from_parts(prefix, u64::MAX - 10)));
+
+        for i in (u64::MAX - 10)..=u64::MAX {
+            assert_eq!(
+                ProcessUniqueId::new(),
+                ProcessUniqueId::from_parts(prefix, i)
             );
         }
+        // The next prefix is either fresh or given back by an exited thread, so it may not start
+        // at 0 (or have room for more than one ID).
         let next = ProcessUniqueId::new();
-        assert!(next.prefix != first_unique_id.prefix);
-        assert!(next.offset == 0);
-        assert!(
-            ProcessUniqueId::new() == ProcessUniqueId {
-                prefix: next.prefix,
-                offset: 1,
-            }
-        );
+        assert_ne!(next.prefix(), prefix);
+        if let Some(offset) = next.offset.checked_add(1) {
+            assert_eq!(
+                ProcessUniqueId::new(),
+                ProcessUniqueId::from_parts(next.prefix(), offset)
+            );
+        }
     }
 
     #[test]
@@ -154,11 +683,10 @@ mod test {
             .map(|_| {
                 thread::spawn(move || {
                     thread::park();
-                    let unique_id = ProcessUniqueId::new();
-                    assert_eq!(unique_id.offset, 0);
-                    unique_id.prefix
+                    ProcessUniqueId::new()
                 })
-            }).collect();
+            })
+            .collect();
 
         // Start them all at once.
         for thread in &threads {
@@ -172,6 +700,309 @@ mod test {
         assert_eq!(old_len, results.len());
     }
 
+    #[test]
+    fn test_reclaim_prefixes() {
+        // One thread at a time, so each can pick up where the last one left off.
+        let mut ids = Vec::new();
+        for _ in 0..100 {
+            ids.extend(
+                thread::spawn(|| (0..3).map(|_| ProcessUniqueId::new()).collect::<Vec<_>>())
+                    .join()
+                    .unwrap(),
+            );
+        }
+        let mut prefixes: Vec<_> = ids.iter().map(|id| id.prefix()).collect();
+        prefixes.sort();
+        prefixes.dedup();
+        assert!(prefixes.len() < 100);
+
+        ids.sort();
+        let old_len = ids.len();
+        ids.dedup();
+        assert_eq!(old_len, ids.len());
+    }
+
+    #[test]
+    fn test_exhausted() {
+        use portable_atomic::AtomicUsize;
+
+        let counter = AtomicUsize::new(usize::MAX - 1);
+        assert_eq!(try_next_prefix(&counter), Ok(usize::MAX - 1));
+        assert_eq!(try_next_prefix(&counter), Err(ExhaustedError(())));
+
+        // The last prefix can still be used up, then we fail without touching the thread's state.
+        #[cfg(feature = "std")]
+        {
+            let mut next = Some(ProcessUniqueId::from_parts(usize::MAX - 1, u64::MAX));
+            assert_eq!(
+                try_advance(&mut next, &counter),
+                Ok(ProcessUniqueId::from_parts(usize::MAX - 1, u64::MAX))
+            );
+            assert_eq!(next, None);
+            assert_eq!(try_advance(&mut next, &counter), Err(ExhaustedError(())));
+            assert_eq!(next, None);
+        }
+
+        #[cfg(feature = "std")]
+        set_local_next(Some(ProcessUniqueId::from_parts(fresh_prefix(), 0)));
+        // Without `std`, other threads may take offsets in between.
+        let id = ProcessUniqueId::try_new().unwrap();
+        let next = ProcessUniqueId::try_new().unwrap();
+        assert_eq!(next.prefix(), id.prefix());
+        assert!(next.offset > id.offset);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_reserve() {
+        // Start on a fresh prefix so there's room for the offsets below.
+        set_local_next(Some(ProcessUniqueId::from_parts(fresh_prefix(), 0)));
+        let before = ProcessUniqueId::new();
+        let range = ProcessUniqueId::reserve(10);
+        let after = ProcessUniqueId::new();
+        assert_eq!(range.len(), 10);
+        assert!(!range.contains(&before));
+        assert!(!range.contains(&after));
+        assert_eq!(after.prefix(), before.prefix());
+        assert_eq!(after.offset, before.offset + 11);
+
+        // Hand the whole block to another thread.
+        let ids: Vec<_> = thread::spawn(move || range.collect()).join().unwrap();
+        assert_eq!(ids.len(), 10);
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(id.prefix(), before.prefix());
+            assert_eq!(id.offset, before.offset + 1 + i as u64);
+        }
+
+        assert!(ProcessUniqueId::reserve(0).is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_reserve_fresh_prefix() {
+        let prefix = fresh_prefix();
+        set_local_next(Some(ProcessUniqueId::from_parts(prefix, u64::MAX - 5)));
+        let range: IdRange = ProcessUniqueId::reserve(100);
+        let mut ids = range.clone();
+        let first_reserved = ids.next().unwrap();
+        assert_ne!(first_reserved.prefix(), prefix);
+        assert_eq!(first_reserved.offset, 0);
+        assert_eq!(ids.next_back().unwrap().offset, 99);
+        assert!(range.contains(&first_reserved));
+        assert!(!ids.contains(&first_reserved));
+        assert_eq!(ids.len(), 98);
+
+        // The thread's own prefix is untouched.
+        assert_eq!(
+            ProcessUniqueId::new(),
+            ProcessUniqueId::from_parts(prefix, u64::MAX - 5)
+        );
+        set_local_next(None);
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[test]
+    fn test_shared_offset() {
+        // Without thread locals, every thread takes offsets under the same prefix.
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                thread::spawn(|| {
+                    (0..1000)
+                        .map(|_| ProcessUniqueId::try_new().unwrap())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut ids: Vec<_> = threads
+            .into_iter()
+            .flat_map(|t| t.join().unwrap())
+            .collect();
+        let prefix = ids[0].prefix();
+        assert!(ids.iter().all(|id| id.prefix() == prefix));
+        ids.sort();
+        let old_len = ids.len();
+        ids.dedup();
+        assert_eq!(old_len, ids.len());
+
+        let range = ProcessUniqueId::reserve(10);
+        assert_eq!(range.len(), 10);
+        assert!(range.clone().all(|id| id.prefix() == prefix));
+        assert!(!range.clone().any(|id| ids.contains(&id)));
+
+        // Too many to fit after the shared offset, so these get a prefix of their own.
+        let mut range = ProcessUniqueId::reserve(u64::MAX);
+        let first = range.next().unwrap();
+        assert_ne!(first.prefix(), prefix);
+        assert_eq!(first.offset, 0);
+        assert_eq!(range.next_back().unwrap().offset, u64::MAX - 1);
+    }
+
+    #[cfg(feature = "serde_support")]
+    #[test]
+    fn test_serde() {
+        extern crate bincode;
+        extern crate serde_json;
+
+        let id = ProcessUniqueId::from_parts(0x1a, 2);
+
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, "\"puid-1a-2\"");
+        assert_eq!(serde_json::from_str::<ProcessUniqueId>(&json).unwrap(), id);
+
+        let bytes = bincode::serialize(&id).unwrap();
+        assert_eq!(bytes.len(), 16);
+        assert_eq!(bincode::deserialize::<ProcessUniqueId>(&bytes).unwrap(), id);
+
+        // What the derived implementation used to produce.
+        #[derive(Serialize)]
+        struct Old {
+            prefix: usize,
+            offset: u64,
+        }
+        let old = Old {
+            prefix: 0x1a,
+            offset: 2,
+        };
+        let old_json = serde_json::to_string(&old).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ProcessUniqueId>(&old_json).unwrap(),
+            id
+        );
+        let old_bytes = bincode::serialize(&old).unwrap();
+        assert_eq!(old_bytes, bytes);
+
+        assert!(serde_json::from_str::<ProcessUniqueId>("\"puid-01-2\"").is_err());
+        assert!(serde_json::from_str::<ProcessUniqueId>(r#"{"prefix":1}"#).is_err());
+
+        // `usize::MAX` isn't a valid prefix.
+        let max = usize::MAX as u64;
+        assert!(bincode::deserialize::<ProcessUniqueId>(&[0xff; 16]).is_err());
+        assert!(serde_json::from_str::<ProcessUniqueId>(&format!("[{},0]", max)).is_err());
+        assert!(serde_json::from_str::<ProcessUniqueId>(&format!(
+            r#"{{"prefix":{},"offset":0}}"#,
+            max
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_bytes() {
+        let mut ids = vec![
+            ProcessUniqueId::new(),
+            ProcessUniqueId::new(),
+            ProcessUniqueId::from_parts(0, u64::MAX),
+            ProcessUniqueId::from_parts(1, 0),
+            ProcessUniqueId::from_parts(0x100, 0xff),
+            ProcessUniqueId::from_parts(usize::MAX - 1, 0),
+        ];
+        for id in &ids {
+            assert_eq!(ProcessUniqueId::from_bytes(id.to_bytes()), Ok(*id));
+        }
+
+        let mut keys: Vec<_> = ids.iter().map(|id| id.to_bytes()).collect();
+        ids.sort();
+        keys.sort();
+        let decoded: Vec<_> = keys
+            .into_iter()
+            .map(|key| ProcessUniqueId::from_bytes(key).unwrap())
+            .collect();
+        assert_eq!(decoded, ids);
+
+        let mut bytes = [0; 16];
+        bytes[7] = 2;
+        bytes[15] = 1;
+        assert_eq!(
+            ProcessUniqueId::from_bytes(bytes),
+            Ok(ProcessUniqueId::from_parts(2, 1))
+        );
+        #[cfg(target_pointer_width = "32")]
+        assert_eq!(
+            ProcessUniqueId::from_bytes([0xff; 16]),
+            Err(ParseIdError::PrefixOverflow)
+        );
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let ids = [
+            ProcessUniqueId::new(),
+            ProcessUniqueId::from_parts(0, 0),
+            ProcessUniqueId::from_parts(usize::MAX - 1, u64::MAX),
+        ];
+        for id in &ids {
+            assert_eq!(id.to_string().parse::<ProcessUniqueId>(), Ok(*id));
+        }
+        assert_eq!(
+            "puid-1a-ff".parse::<ProcessUniqueId>(),
+            Ok(ProcessUniqueId::from_parts(0x1a, 0xff))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        fn parse(s: &str) -> Result<ProcessUniqueId, ParseIdError> {
+            s.parse()
+        }
+        assert_eq!(parse("1-2"), Err(ParseIdError::MissingTag));
+        assert_eq!(parse("PUID-1-2"), Err(ParseIdError::MissingTag));
+        assert_eq!(parse("puid-1"), Err(ParseIdError::MissingSeparator));
+        assert_eq!(parse("puid--2"), Err(ParseIdError::InvalidPrefix));
+        assert_eq!(parse("puid-01-2"), Err(ParseIdError::InvalidPrefix));
+        assert_eq!(parse("puid-A-2"), Err(ParseIdError::InvalidPrefix));
+        assert_eq!(parse("puid-+1-2"), Err(ParseIdError::InvalidPrefix));
+        assert_eq!(parse("puid-1-"), Err(ParseIdError::InvalidOffset));
+        assert_eq!(parse("puid-1-2-3"), Err(ParseIdError::InvalidOffset));
+        assert_eq!(parse("puid-1-00"), Err(ParseIdError::InvalidOffset));
+        assert_eq!(
+            parse("puid-1-10000000000000000"),
+            Err(ParseIdError::OffsetOverflow)
+        );
+        assert_eq!(
+            parse("puid-10000000000000000-1"),
+            Err(ParseIdError::PrefixOverflow)
+        );
+        assert_eq!(
+            parse("puid-10000000000000000g-1"),
+            Err(ParseIdError::InvalidPrefix)
+        );
+        assert_eq!(
+            parse("puid-1-10000000000000000g"),
+            Err(ParseIdError::InvalidOffset)
+        );
+        #[cfg(target_pointer_width = "32")]
+        assert_eq!(parse("puid-100000000-1"), Err(ParseIdError::PrefixOverflow));
+        assert_eq!(
+            parse(&format!("puid-{:x}-1", usize::MAX)),
+            Err(ParseIdError::PrefixOverflow)
+        );
+    }
+
+    #[test]
+    fn test_niche() {
+        use std::mem::size_of;
+
+        assert_eq!(
+            size_of::<Option<ProcessUniqueId>>(),
+            size_of::<ProcessUniqueId>()
+        );
+
+        // The representation doesn't leak out.
+        let id = ProcessUniqueId::from_parts(0, 5);
+        assert_eq!(id.prefix(), 0);
+        assert_eq!(
+            format!("{:?}", id),
+            "ProcessUniqueId { prefix: 0, offset: 5 }"
+        );
+        assert!(id < ProcessUniqueId::from_parts(1, 0));
+        assert_eq!(ProcessUniqueId::try_from_parts(usize::MAX as u64, 0), None);
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&(usize::MAX as u64).to_be_bytes());
+        assert_eq!(
+            ProcessUniqueId::from_bytes(bytes),
+            Err(ParseIdError::PrefixOverflow)
+        );
+    }
+
     #[bench]
     fn bench_next_global(b: &mut Bencher) {
         b.iter(|| {
@@ -228,6 +1059,14 @@ mod test {
         });
     }
 
+    #[cfg(feature = "std")]
+    #[bench]
+    fn bench_uuid_v7(b: &mut Bencher) {
+        b.iter(|| {
+            UuidV7::new();
+        });
+    }
+
     #[bench]
     fn bench_unique_id_threaded(b: &mut Bencher) {
         let pool = ThreadPool::new(4usize);
@@ -245,4 +1084,31 @@ mod test {
             rx.iter().take(4).count();
         });
     }
+
+    #[cfg(feature = "std")]
+    #[bench]
+    fn bench_sequential_id(b: &mut Bencher) {
+        b.iter(|| {
+            SequentialId::new();
+        });
+    }
+
+    #[cfg(feature = "std")]
+    #[bench]
+    fn bench_sequential_id_threaded(b: &mut Bencher) {
+        let pool = ThreadPool::new(4usize);
+        b.iter(|| {
+            let (tx, rx) = channel();
+            for _ in 0..4 {
+                let tx = tx.clone();
+                pool.execute(move || {
+                    for _ in 0..1000 {
+                        SequentialId::new();
+                    }
+                    tx.send(()).unwrap();
+                });
+            }
+            rx.iter().take(4).count();
+        });
+    }
 }
@@ -0,0 +1,154 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::cell::Cell;
+use std::fmt;
+
+use portable_atomic::{AtomicU64, Ordering};
+
+const MAX_BLOCK_SIZE: u64 = 1024;
+
+// The end of the most recently reserved block.
+static NEXT_BLOCK: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // The next ID, the end of this thread's block, and the size of the block.
+    static LOCAL_BLOCK: Cell<(u64, u64, u64)> = const { Cell::new((0, 0, 0)) };
+}
+
+fn reserve_block(size: u64) -> u64 {
+    NEXT_BLOCK
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |next| {
+            next.checked_add(size)
+        })
+        .expect("Snow Crash: ran out of sequential IDs!")
+}
+
+/// A process unique ID that sorts in creation order.
+///
+/// `ProcessUniqueId`s compare by prefix first, so an ID created later on one thread can sort
+/// before an ID created earlier on another. `SequentialId`s don't have this problem: if the
+/// creation of one ID happens before the creation of another (in the sense of the memory model,
+/// e.g. they're created on the same thread or the first was sent to the thread creating the
+/// second), the first compares less than the second. IDs created concurrently may compare either
+/// way.
+///
+/// # Performance
+///
+/// Threads reserve blocks of up to 1024 IDs from a shared counter. A thread only hands out IDs
+/// from its block while it holds the most recently reserved block (checked with a plain atomic
+/// load), so one thread creating IDs in a loop is nearly as fast as `ProcessUniqueId::new`.
+///
+/// Reservation is only batched while a thread has the counter to itself. An ID has to be greater
+/// than every ID another thread created before it, so once another thread has reserved a block,
+/// the next ID has to come from a new block. Threads that keep taking turns therefore pay one
+/// contended atomic increment per ID, like a plain shared counter would (compare
+/// `bench_sequential_id_threaded` with `bench_next_global_threaded`). A thread whose block was
+/// cut short halves the size of its next block (and a thread that uses up its block doubles it)
+/// so taking turns doesn't also skip large runs of IDs.
+///
+/// # Limits
+///
+/// A new block skips whatever was left of the previous one. Thanks to the shrinking blocks, at
+/// most about two IDs are skipped for every one handed out, so this can run out after (at
+/// worst) roughly 2^62 IDs, at which point `new()` panics.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct SequentialId(u64);
+
+impl SequentialId {
+    /// Create a new ID, greater than every ID whose creation happened before this call.
+    ///
+    /// **panics** if there are no more IDs available.
+    #[inline]
+    pub fn new() -> Self {
+        LOCAL_BLOCK.with(|block| {
+            let (next, end, size) = block.get();
+            // If the shared counter still ends at our block, no other thread has reserved a block
+            // (and so handed out larger IDs) since we did. Relaxed is enough: if another thread's
+            // reservation happened before this call, coherence guarantees we see it here.
+            if next < end && NEXT_BLOCK.load(Ordering::Relaxed) == end {
+                block.set((next + 1, end, size));
+                SequentialId(next)
+            } else {
+                let size = if next < end {
+                    (size / 2).max(1)
+                } else {
+                    (size * 2).clamp(1, MAX_BLOCK_SIZE)
+                };
+                let start = reserve_block(size);
+                block.set((start + 1, start + size, size));
+                SequentialId(start)
+            }
+        })
+    }
+
+    /// The raw 64 bit value of this ID.
+    #[inline]
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for SequentialId {
+    #[inline]
+    fn default() -> Self {
+        SequentialId::new()
+    }
+}
+
+impl From<SequentialId> for u64 {
+    #[inline]
+    fn from(id: SequentialId) -> u64 {
+        id.0
+    }
+}
+
+impl fmt::Display for SequentialId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "seq-{:x}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SequentialId;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    #[test]
+    fn test_sequential_unthreaded() {
+        let mut prev = SequentialId::new();
+        for _ in 0..5000 {
+            let next = SequentialId::new();
+            assert!(next > prev);
+            prev = next;
+        }
+    }
+
+    #[test]
+    fn test_sequential_threaded() {
+        // The lock orders the creations so every ID must be greater than the last one.
+        let last = Arc::new(Mutex::new(SequentialId::new()));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let last = last.clone();
+                thread::spawn(move || {
+                    for _ in 0..5000 {
+                        let mut last = last.lock().unwrap();
+                        let next = SequentialId::new();
+                        assert!(next > *last);
+                        *last = next;
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+}
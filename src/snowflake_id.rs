@@ -0,0 +1,276 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use std::fmt;
+use std::time::SystemTime;
+
+use portable_atomic::{AtomicU64, Ordering};
+
+const TIMESTAMP_BITS: u32 = 41;
+const WORKER_ID_BITS: u32 = 10;
+const SEQUENCE_BITS: u32 = 12;
+
+const MAX_TIMESTAMP: u64 = (1 << TIMESTAMP_BITS) - 1;
+const MAX_SEQUENCE: u64 = (1 << SEQUENCE_BITS) - 1;
+
+const TIMESTAMP_OVERFLOW: &str =
+    "Snow Crash: the snowflake timestamp overflowed, pick a newer epoch!";
+
+/// The largest worker ID a `SnowflakeGenerator` accepts.
+pub const MAX_WORKER_ID: u16 = (1 << WORKER_ID_BITS) - 1;
+
+/// A 64 bit, time ordered ID in the style of Twitter's snowflake.
+///
+/// From most to least significant bit, an ID consists of:
+///
+/// 1. One unused bit, always zero (so the ID also fits in an `i64`).
+/// 2. A 41 bit timestamp in milliseconds since the generator's epoch.
+/// 3. A 10 bit worker ID.
+/// 4. A 12 bit sequence number, reset every millisecond.
+///
+/// IDs therefore sort by creation time (to the millisecond) and, as long as every generator uses
+/// a distinct worker ID and the same epoch, are unique across machines.
+///
+/// # Limits
+///
+/// Each worker can create 4096 IDs per millisecond; `SnowflakeGenerator::generate` waits for the
+/// next millisecond when it runs out. The timestamp overflows about 69 years after the epoch.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub struct SnowflakeId(u64);
+
+impl SnowflakeId {
+    /// Reinterpret a raw 64 bit value as a snowflake ID.
+    ///
+    /// Returns `None` if the (unused) most significant bit is set.
+    #[inline]
+    pub fn from_u64(raw: u64) -> Option<Self> {
+        if raw >> 63 == 0 {
+            Some(SnowflakeId(raw))
+        } else {
+            None
+        }
+    }
+
+    /// The raw 64 bit value of this ID.
+    #[inline]
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// Milliseconds between the generator's epoch and the creation of this ID.
+    #[inline]
+    pub fn timestamp(self) -> u64 {
+        self.0 >> (WORKER_ID_BITS + SEQUENCE_BITS)
+    }
+
+    /// The ID of the worker that created this ID.
+    #[inline]
+    pub fn worker_id(self) -> u16 {
+        ((self.0 >> SEQUENCE_BITS) & u64::from(MAX_WORKER_ID)) as u16
+    }
+
+    /// The position of this ID among the IDs its worker created in the same millisecond.
+    #[inline]
+    pub fn sequence(self) -> u16 {
+        (self.0 & MAX_SEQUENCE) as u16
+    }
+}
+
+impl From<SnowflakeId> for u64 {
+    #[inline]
+    fn from(id: SnowflakeId) -> u64 {
+        id.0
+    }
+}
+
+impl fmt::Display for SnowflakeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// Creates `SnowflakeId`s for a single worker.
+///
+/// A generator can be shared between threads; IDs are unique and strictly increasing across all
+/// callers of `generate` on the same generator.
+#[derive(Debug)]
+pub struct SnowflakeGenerator {
+    epoch: SystemTime,
+    worker_id: u16,
+    // The timestamp and sequence number of the last ID handed out, packed as
+    // `timestamp << SEQUENCE_BITS | sequence`.
+    last: AtomicU64,
+}
+
+impl SnowflakeGenerator {
+    /// Create a generator for the given worker, counting time from `epoch`.
+    ///
+    /// **panics** if `worker_id` is greater than `MAX_WORKER_ID` or if `epoch` is in the future.
+    pub fn new(worker_id: u16, epoch: SystemTime) -> Self {
+        assert!(
+            worker_id <= MAX_WORKER_ID,
+            "snowflake worker IDs must be at most {}",
+            MAX_WORKER_ID
+        );
+        assert!(
+            epoch <= SystemTime::now(),
+            "the snowflake epoch must not be in the future"
+        );
+        SnowflakeGenerator {
+            epoch,
+            worker_id,
+            last: AtomicU64::new(0),
+        }
+    }
+
+    /// The worker ID embedded in every ID this generator creates.
+    #[inline]
+    pub fn worker_id(&self) -> u16 {
+        self.worker_id
+    }
+
+    /// The epoch ID timestamps are relative to.
+    #[inline]
+    pub fn epoch(&self) -> SystemTime {
+        self.epoch
+    }
+
+    /// Create a new ID.
+    ///
+    /// If the system clock goes backwards, this keeps counting from the last timestamp it saw so
+    /// IDs never repeat or go backwards, moving on to the next (logical) millisecond whenever the
+    /// sequence runs out. Only when the clock really is at the current millisecond and its
+    /// sequence is used up does this spin until the clock moves on.
+    ///
+    /// **panics** if the timestamp no longer fits in 41 bits.
+    pub fn generate(&self) -> SnowflakeId {
+        let mut last = self.last.load(Ordering::Relaxed);
+        loop {
+            let now = self.now();
+            let last_ms = last >> SEQUENCE_BITS;
+            let next = if now > last_ms {
+                now << SEQUENCE_BITS
+            } else if last & MAX_SEQUENCE < MAX_SEQUENCE {
+                last + 1
+            } else if now < last_ms {
+                // Behind the last timestamp we handed out; borrow the next millisecond.
+                assert!(last_ms < MAX_TIMESTAMP, "{}", TIMESTAMP_OVERFLOW);
+                (last_ms + 1) << SEQUENCE_BITS
+            } else {
+                std::hint::spin_loop();
+                last = self.last.load(Ordering::Relaxed);
+                continue;
+            };
+            match self
+                .last
+                .compare_exchange_weak(last, next, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => {
+                    return SnowflakeId(
+                        (next >> SEQUENCE_BITS) << (WORKER_ID_BITS + SEQUENCE_BITS)
+                            | u64::from(self.worker_id) << SEQUENCE_BITS
+                            | next & MAX_SEQUENCE,
+                    );
+                }
+                Err(actual) => last = actual,
+            }
+        }
+    }
+
+    fn now(&self) -> u64 {
+        // A clock that has gone back past the epoch is treated like any other backwards step.
+        let millis = SystemTime::now()
+            .duration_since(self.epoch)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        assert!(
+            millis <= u128::from(MAX_TIMESTAMP),
+            "{}",
+            TIMESTAMP_OVERFLOW
+        );
+        millis as u64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SnowflakeGenerator, SnowflakeId, MAX_WORKER_ID};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_fields() {
+        let epoch = SystemTime::now() - Duration::from_secs(10);
+        let gen = SnowflakeGenerator::new(MAX_WORKER_ID, epoch);
+        let id = gen.generate();
+        assert_eq!(id.worker_id(), MAX_WORKER_ID);
+        assert!(id.timestamp() >= 10_000);
+        assert_eq!(SnowflakeId::from_u64(id.as_u64()), Some(id));
+        assert_eq!(SnowflakeId::from_u64(1 << 63), None);
+    }
+
+    #[test]
+    fn test_ordered() {
+        let gen = SnowflakeGenerator::new(3, SystemTime::UNIX_EPOCH);
+        let mut prev = gen.generate();
+        for _ in 0..10_000 {
+            let next = gen.generate();
+            assert!(next > prev);
+            assert!(next.timestamp() >= prev.timestamp());
+            assert_eq!(next.worker_id(), 3);
+            prev = next;
+        }
+    }
+
+    #[test]
+    fn test_threaded() {
+        let gen = Arc::new(SnowflakeGenerator::new(1, SystemTime::UNIX_EPOCH));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let gen = gen.clone();
+                thread::spawn(move || (0..10_000).map(|_| gen.generate()).collect::<Vec<_>>())
+            })
+            .collect();
+        let mut results: Vec<_> = threads
+            .into_iter()
+            .flat_map(|t| t.join().unwrap())
+            .collect();
+        results.sort();
+        let old_len = results.len();
+        results.dedup();
+        assert_eq!(old_len, results.len());
+    }
+
+    #[test]
+    fn test_clock_backwards() {
+        use super::{MAX_SEQUENCE, SEQUENCE_BITS};
+        use portable_atomic::Ordering;
+
+        let gen = SnowflakeGenerator::new(0, SystemTime::UNIX_EPOCH);
+        // Pretend we've already used up a millisecond an hour from now.
+        let ahead = gen.now() + 3_600_000;
+        gen.last
+            .store(ahead << SEQUENCE_BITS | MAX_SEQUENCE, Ordering::Relaxed);
+        let mut prev = gen.generate();
+        assert_eq!(prev.timestamp(), ahead + 1);
+        assert_eq!(prev.sequence(), 0);
+        for _ in 0..10_000 {
+            let next = gen.generate();
+            assert!(next > prev);
+            prev = next;
+        }
+        assert_eq!(prev.timestamp(), ahead + 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bad_worker_id() {
+        SnowflakeGenerator::new(MAX_WORKER_ID + 1, SystemTime::UNIX_EPOCH);
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Test helpers for controlling the IDs `ProcessUniqueId::new` creates.
+//!
+//! Only available with the `testing` feature.
+use std::cell::RefCell;
+
+use crate::process_unique_id::ProcessUniqueId;
+
+type Source = Box<dyn Iterator<Item = ProcessUniqueId>>;
+
+thread_local! {
+    static OVERRIDE: RefCell<Option<Source>> = const { RefCell::new(None) };
+}
+
+/// Take the next ID from the current thread's override, if any.
+///
+/// **panics** if the override has run out of IDs.
+pub(crate) fn next_override() -> Option<ProcessUniqueId> {
+    // There's no override while thread locals are being destroyed.
+    OVERRIDE
+        .try_with(|cell| {
+            // Take the source out while calling it in case it creates IDs itself.
+            let mut source = cell.borrow_mut().take()?;
+            let id = source.next();
+            *cell.borrow_mut() = Some(source);
+            Some(id.expect("with_ids: ran out of IDs"))
+        })
+        .ok()
+        .flatten()
+}
+
+// Puts the previous override back, even if the closure panics.
+struct Restore(Option<Source>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        OVERRIDE.with(|cell| *cell.borrow_mut() = previous);
+    }
+}
+
+/// Call `f`, making `ProcessUniqueId::new` (and `try_new`, `Id::new`, etc.) on the current thread
+/// return the IDs from `ids` until `f` returns.
+///
+/// Calls can be nested; the innermost one wins. IDs created on other threads and IDs reserved
+/// with `ProcessUniqueId::reserve` aren't affected.
+///
+/// **panics** if `f` creates more IDs than `ids` yields.
+///
+/// ```
+/// use snowflake::{testing, DeterministicGenerator, ProcessUniqueId};
+///
+/// let expected: Vec<ProcessUniqueId> = DeterministicGenerator::new(0, 0).take(2).collect();
+/// let ids = testing::with_ids(DeterministicGenerator::new(0, 0), || {
+///     (ProcessUniqueId::new(), ProcessUniqueId::new())
+/// });
+/// assert_eq!(ids, (expected[0], expected[1]));
+/// ```
+pub fn with_ids<I, F, R>(ids: I, f: F) -> R
+where
+    I: IntoIterator<Item = ProcessUniqueId>,
+    I::IntoIter: 'static,
+    F: FnOnce() -> R,
+{
+    let source: Source = Box::new(ids.into_iter());
+    let _restore = Restore(OVERRIDE.with(|cell| cell.borrow_mut().replace(source)));
+    f()
+}
+
+#[cfg(test)]
+mod test {
+    use super::with_ids;
+    use crate::process_unique_id::ProcessUniqueId;
+    use crate::typed_id::Id;
+    use std::panic;
+    use std::thread;
+
+    fn id(offset: u64) -> ProcessUniqueId {
+        ProcessUniqueId::from_parts(0x5eed, offset)
+    }
+
+    #[test]
+    fn test_with_ids() {
+        let (a, b, c) = with_ids(vec![id(0), id(1), id(2)], || {
+            (
+                ProcessUniqueId::new(),
+                ProcessUniqueId::try_new(),
+                Id::<()>::new(),
+            )
+        });
+        assert_eq!(a, id(0));
+        assert_eq!(b, Ok(id(1)));
+        assert_eq!(c.untyped(), id(2));
+
+        // Only while the closure runs and only on this thread.
+        assert_ne!(ProcessUniqueId::new().prefix(), 0x5eed);
+        with_ids(vec![id(0)], || {
+            let other = thread::spawn(ProcessUniqueId::new).join().unwrap();
+            assert_ne!(other, id(0));
+        });
+    }
+
+    #[test]
+    fn test_nested() {
+        with_ids((0..).map(id), || {
+            assert_eq!(ProcessUniqueId::new(), id(0));
+            with_ids(vec![id(100)], || {
+                assert_eq!(ProcessUniqueId::new(), id(100))
+            });
+            assert_eq!(ProcessUniqueId::new(), id(1));
+
+            // Restored even if the inner closure panics.
+            let result = panic::catch_unwind(|| with_ids(vec![], ProcessUniqueId::new));
+            assert!(result.is_err());
+            assert_eq!(ProcessUniqueId::new(), id(2));
+        });
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::str::FromStr;
+
+use crate::process_unique_id::{ParseIdError, ProcessUniqueId};
+
+/// A `ProcessUniqueId` tagged with the type of the thing it identifies.
+///
+/// `Id<User>` and `Id<Order>` are distinct types, so one can't be passed where the other is
+/// expected. The tag is zero sized and `T` doesn't need to implement anything; `Id<T>` is `Copy`,
+/// `Ord`, `Hash`, etc. regardless of `T`.
+///
+/// ```
+/// use snowflake::{Id, ProcessUniqueId};
+///
+/// struct User;
+///
+/// let user: Id<User> = Id::new();
+/// let untyped: ProcessUniqueId = user.untyped();
+/// assert_eq!(Id::<User>::from_untyped(untyped), user);
+/// ```
+pub struct Id<T: ?Sized> {
+    id: ProcessUniqueId,
+    // `fn() -> T` keeps `Id<T>` `Send`, `Sync` and covariant no matter what `T` is.
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: ?Sized> Id<T> {
+    /// Create a new unique ID.
+    ///
+    /// **panics** under the same conditions as `ProcessUniqueId::new`.
+    #[inline]
+    pub fn new() -> Self {
+        Id::from_untyped(ProcessUniqueId::new())
+    }
+
+    /// Tag an untyped ID.
+    #[inline]
+    pub fn from_untyped(id: ProcessUniqueId) -> Self {
+        Id {
+            id,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Strip the type tag.
+    #[inline]
+    pub fn untyped(self) -> ProcessUniqueId {
+        self.id
+    }
+}
+
+impl<T: ?Sized> From<Id<T>> for ProcessUniqueId {
+    #[inline]
+    fn from(id: Id<T>) -> ProcessUniqueId {
+        id.id
+    }
+}
+
+impl<T: ?Sized> Default for Id<T> {
+    #[inline]
+    fn default() -> Self {
+        Id::new()
+    }
+}
+
+impl<T: ?Sized> Clone for Id<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for Id<T> {}
+
+impl<T: ?Sized> PartialEq for Id<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T: ?Sized> Eq for Id<T> {}
+
+impl<T: ?Sized> PartialOrd for Id<T> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: ?Sized> Ord for Id<T> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+impl<T: ?Sized> Hash for Id<T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state)
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Id<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Id").field(&self.id).finish()
+    }
+}
+
+impl<T: ?Sized> fmt::Display for Id<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.id, f)
+    }
+}
+
+impl<T: ?Sized> FromStr for Id<T> {
+    type Err = ParseIdError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, ParseIdError> {
+        s.parse().map(Id::from_untyped)
+    }
+}
+
+#[cfg(feature = "serde_support")]
+impl<T: ?Sized> serde::Serialize for Id<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.id.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde_support")]
+impl<'de, T: ?Sized> serde::Deserialize<'de> for Id<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        ProcessUniqueId::deserialize(deserializer).map(Id::from_untyped)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Id;
+    use crate::process_unique_id::ProcessUniqueId;
+    use std::collections::HashSet;
+
+    // Deliberately implements nothing.
+    struct User;
+
+    #[test]
+    fn test_traits_without_bounds() {
+        let a: Id<User> = Id::new();
+        let b = a;
+        assert_eq!(a, b);
+        assert!(Id::<User>::new() > a);
+
+        let mut set = HashSet::new();
+        set.insert(a);
+        assert!(set.contains(&b));
+
+        assert_eq!(a.to_string(), a.untyped().to_string());
+        assert_eq!(a.to_string().parse::<Id<User>>(), Ok(a));
+    }
+
+    #[test]
+    fn test_untyped_round_trip() {
+        let id = ProcessUniqueId::new();
+        let typed = Id::<str>::from_untyped(id);
+        assert_eq!(ProcessUniqueId::from(typed), id);
+        assert_eq!(typed.untyped(), id);
+    }
+}
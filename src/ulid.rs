@@ -0,0 +1,398 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+use core::fmt;
+use core::str::FromStr;
+
+#[cfg(feature = "std")]
+use std::time::SystemTime;
+
+const RANDOM_BITS: u32 = 80;
+const RANDOM_MASK: u128 = (1 << RANDOM_BITS) - 1;
+const MAX_TIMESTAMP: u64 = (1 << 48) - 1;
+
+const ENCODED_LEN: usize = 26;
+// Crockford's base32 alphabet.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// A universally unique lexicographically sortable identifier.
+///
+/// ULIDs are 128 bits wide: a 48 bit timestamp in milliseconds since the unix epoch followed by
+/// 80 random bits. They sort by creation time (to the millisecond) and, thanks to the random
+/// bits, are unique across processes and machines without any coordination. Their string form is
+/// 26 characters of Crockford's base32 (see <https://github.com/ulid/spec>).
+///
+/// Use `Ulid::new` for one-off IDs and `UlidGenerator` to create many (optionally strictly
+/// increasing) IDs.
+///
+/// With `serde_support`, ULIDs serialize as their string form in human readable formats and as a
+/// `(u64, u64)` tuple of the high and low halves in binary formats.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Ulid(u128);
+
+impl Ulid {
+    /// Create a new ULID from the current time and the OS random source.
+    ///
+    /// **panics** if the OS random source can't be read.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn new() -> Self {
+        Ulid::from_parts(now(), random())
+    }
+
+    /// Build a ULID from a timestamp (milliseconds since the unix epoch) and random bits.
+    ///
+    /// Only the low 48 bits of `timestamp_ms` and the low 80 bits of `random` are used.
+    #[inline]
+    pub fn from_parts(timestamp_ms: u64, random: u128) -> Self {
+        Ulid(u128::from(timestamp_ms & MAX_TIMESTAMP) << RANDOM_BITS | random & RANDOM_MASK)
+    }
+
+    /// The time this ULID was created, in milliseconds since the unix epoch.
+    #[inline]
+    pub fn timestamp_ms(self) -> u64 {
+        (self.0 >> RANDOM_BITS) as u64
+    }
+
+    /// The 80 random bits of this ULID.
+    #[inline]
+    pub fn random(self) -> u128 {
+        self.0 & RANDOM_MASK
+    }
+
+    /// The 128 bit value of this ULID.
+    #[inline]
+    pub fn as_u128(self) -> u128 {
+        self.0
+    }
+
+    /// Reinterpret a 128 bit value as a ULID.
+    #[inline]
+    pub fn from_u128(value: u128) -> Self {
+        Ulid(value)
+    }
+
+    /// Encode this ULID as 16 big endian bytes (the standard binary form).
+    #[inline]
+    pub fn to_bytes(self) -> [u8; 16] {
+        self.0.to_be_bytes()
+    }
+
+    /// Decode a ULID encoded with `to_bytes`.
+    #[inline]
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Ulid(u128::from_be_bytes(bytes))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for Ulid {
+    #[inline]
+    fn default() -> Self {
+        Ulid::new()
+    }
+}
+
+impl From<Ulid> for u128 {
+    #[inline]
+    fn from(ulid: Ulid) -> u128 {
+        ulid.0
+    }
+}
+
+impl fmt::Display for Ulid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut buf = [0; ENCODED_LEN];
+        let mut value = self.0;
+        for c in buf.iter_mut().rev() {
+            *c = ALPHABET[(value & 0x1f) as usize];
+            value >>= 5;
+        }
+        f.write_str(core::str::from_utf8(&buf).unwrap())
+    }
+}
+
+/// An error returned when parsing a `Ulid` from a string fails.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ParseUlidError {
+    /// The string isn't 26 characters long.
+    InvalidLength,
+    /// The string contains a character outside of Crockford's base32 alphabet.
+    InvalidCharacter,
+    /// The string encodes a value that doesn't fit in 128 bits (the first character is greater
+    /// than `7`).
+    Overflow,
+}
+
+impl fmt::Display for ParseUlidError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ParseUlidError::InvalidLength => "ULID must be 26 characters long",
+            ParseUlidError::InvalidCharacter => "invalid character in ULID",
+            ParseUlidError::Overflow => "ULID is too large",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseUlidError {}
+
+impl FromStr for Ulid {
+    type Err = ParseUlidError;
+
+    /// Parse a ULID from its string form. Parsing is case insensitive.
+    fn from_str(s: &str) -> Result<Self, ParseUlidError> {
+        let bytes = s.as_bytes();
+        if bytes.len() != ENCODED_LEN {
+            return Err(ParseUlidError::InvalidLength);
+        }
+        let mut value: u128 = 0;
+        for &b in bytes {
+            let digit = ALPHABET
+                .iter()
+                .position(|&c| c == b.to_ascii_uppercase())
+                .ok_or(ParseUlidError::InvalidCharacter)?;
+            value = value << 5 | digit as u128;
+        }
+        // 26 characters hold 130 bits; the first may only use the low three.
+        if bytes[0].to_ascii_uppercase() > b'7' {
+            return Err(ParseUlidError::Overflow);
+        }
+        Ok(Ulid(value))
+    }
+}
+
+#[cfg(feature = "std")]
+fn now() -> u64 {
+    let millis = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    assert!(
+        millis <= u128::from(MAX_TIMESTAMP),
+        "ULID timestamps run out in the year 10889"
+    );
+    millis as u64
+}
+
+#[cfg(feature = "std")]
+fn random() -> u128 {
+    let mut bytes = [0; 16];
+    getrandom::fill(&mut bytes[6..]).expect("failed to read from the OS random source");
+    u128::from_be_bytes(bytes)
+}
+
+/// Creates `Ulid`s.
+///
+/// By default, every ULID gets fresh random bits so ULIDs created in the same millisecond sort
+/// randomly. In monotonic mode (`UlidGenerator::monotonic`), a ULID created in the same
+/// millisecond as the previous one (or when the clock has gone backwards) instead gets the
+/// previous ULID's random bits plus one, so every ULID from the generator is strictly greater
+/// than the last.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub struct UlidGenerator {
+    monotonic: bool,
+    last: Option<Ulid>,
+}
+
+#[cfg(feature = "std")]
+impl UlidGenerator {
+    /// Create a generator that draws fresh random bits for every ULID.
+    #[inline]
+    pub fn new() -> Self {
+        UlidGenerator {
+            monotonic: false,
+            last: None,
+        }
+    }
+
+    /// Create a generator whose ULIDs strictly increase.
+    #[inline]
+    pub fn monotonic() -> Self {
+        UlidGenerator {
+            monotonic: true,
+            last: None,
+        }
+    }
+
+    /// Create a new ULID.
+    ///
+    /// In monotonic mode, if the clock goes backwards, this keeps counting from the last timestamp
+    /// it used, moving on to the next (logical) millisecond whenever the random part runs out.
+    /// Only when all 2^80 ULIDs for the clock's current millisecond have been used up does this
+    /// spin until the clock moves on.
+    ///
+    /// **panics** if the OS random source can't be read.
+    pub fn generate(&mut self) -> Ulid {
+        let ulid = loop {
+            let now = now();
+            match self.last {
+                Some(last) if self.monotonic && now <= last.timestamp_ms() => {
+                    if last.random() < RANDOM_MASK {
+                        break Ulid(last.0 + 1);
+                    }
+                    if now < last.timestamp_ms() {
+                        // Behind the last timestamp we used; borrow the next millisecond.
+                        assert!(
+                            last.timestamp_ms() < MAX_TIMESTAMP,
+                            "ULID timestamps run out in the year 10889"
+                        );
+                        break Ulid::from_parts(last.timestamp_ms() + 1, random());
+                    }
+                    std::hint::spin_loop();
+                }
+                _ => break Ulid::from_parts(now, random()),
+            }
+        };
+        self.last = Some(ulid);
+        ulid
+    }
+}
+
+#[cfg(feature = "serde_support")]
+mod serde_impl {
+    use core::fmt;
+    use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+    use serde::ser::{Serialize, Serializer};
+
+    use super::Ulid;
+
+    impl Serialize for Ulid {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if serializer.is_human_readable() {
+                serializer.collect_str(self)
+            } else {
+                ((self.0 >> 64) as u64, self.0 as u64).serialize(serializer)
+            }
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Ulid {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            if deserializer.is_human_readable() {
+                deserializer.deserialize_str(UlidVisitor)
+            } else {
+                deserializer.deserialize_tuple(2, UlidVisitor)
+            }
+        }
+    }
+
+    struct UlidVisitor;
+
+    impl<'de> Visitor<'de> for UlidVisitor {
+        type Value = Ulid;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a ULID")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Ulid, E> {
+            v.parse().map_err(E::custom)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Ulid, A::Error> {
+            let high: u64 = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            let low: u64 = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+            Ok(Ulid(u128::from(high) << 64 | u128::from(low)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ParseUlidError, Ulid};
+    #[cfg(feature = "std")]
+    use super::{UlidGenerator, RANDOM_MASK};
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(Ulid::from_u128(0).to_string(), "00000000000000000000000000");
+        assert_eq!(
+            Ulid::from_u128(u128::MAX).to_string(),
+            "7ZZZZZZZZZZZZZZZZZZZZZZZZZ"
+        );
+        let ulid = Ulid::from_parts(1469918176385, 0);
+        assert_eq!(ulid.to_string(), "01ARYZ6S410000000000000000");
+        assert_eq!(ulid.timestamp_ms(), 1469918176385);
+
+        #[cfg(feature = "std")]
+        {
+            let ulid = Ulid::new();
+            assert_eq!(ulid.to_string().parse(), Ok(ulid));
+            assert_eq!(ulid.to_string().to_lowercase().parse(), Ok(ulid));
+            assert_eq!(Ulid::from_bytes(ulid.to_bytes()), ulid);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        fn parse(s: &str) -> Result<Ulid, ParseUlidError> {
+            s.parse()
+        }
+        assert_eq!(parse(""), Err(ParseUlidError::InvalidLength));
+        assert_eq!(
+            parse("01ARYZ6S41000000000000000"),
+            Err(ParseUlidError::InvalidLength)
+        );
+        assert_eq!(
+            parse("01ARYZ6S4100000000000000U0"),
+            Err(ParseUlidError::InvalidCharacter)
+        );
+        assert_eq!(
+            parse("80000000000000000000000000"),
+            Err(ParseUlidError::Overflow)
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_monotonic() {
+        let mut gen = UlidGenerator::monotonic();
+        let mut prev = gen.generate();
+        for _ in 0..10_000 {
+            let next = gen.generate();
+            assert!(next > prev);
+            if next.timestamp_ms() == prev.timestamp_ms() {
+                assert_eq!(next.random(), prev.random() + 1);
+            }
+            prev = next;
+        }
+
+        // Saturated random bits wait for the next millisecond.
+        let last = Ulid::from_parts(prev.timestamp_ms(), RANDOM_MASK);
+        gen.last = Some(last);
+        assert!(gen.generate().timestamp_ms() > last.timestamp_ms());
+
+        // After the clock goes backwards, saturated random bits borrow the next millisecond.
+        let ahead = Ulid::from_parts(prev.timestamp_ms() + 3_600_000, RANDOM_MASK);
+        gen.last = Some(ahead);
+        let next = gen.generate();
+        assert_eq!(next.timestamp_ms(), ahead.timestamp_ms() + 1);
+        assert!(gen.generate() > next);
+    }
+
+    #[cfg(all(feature = "std", feature = "serde_support"))]
+    #[test]
+    fn test_serde() {
+        extern crate bincode;
+        extern crate serde_json;
+
+        let ulid = Ulid::new();
+        let json = serde_json::to_string(&ulid).unwrap();
+        assert_eq!(json, format!("\"{}\"", ulid));
+        assert_eq!(serde_json::from_str::<Ulid>(&json).unwrap(), ulid);
+
+        let bytes = bincode::serialize(&ulid).unwrap();
+        assert_eq!(bytes.len(), 16);
+        assert_eq!(bincode::deserialize::<Ulid>(&bytes).unwrap(), ulid);
+    }
+}
@@ -0,0 +1,285 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+#[cfg(feature = "std")]
+use std::cell::Cell;
+#[cfg(feature = "std")]
+use std::time::SystemTime;
+
+use core::fmt;
+
+#[cfg(feature = "std")]
+use crate::splitmix::splitmix64;
+
+const VERSION: u128 = 0x7 << 76;
+const VARIANT: u128 = 0b10 << 62;
+const VERSION_MASK: u128 = 0xf << 76;
+const VARIANT_MASK: u128 = 0b11 << 62;
+
+// The counter is split between the 12 bit `rand_a` field and the top 30 bits of `rand_b`.
+#[cfg(feature = "std")]
+const MAX_COUNTER: u64 = (1 << 42) - 1;
+// Start every millisecond somewhere in the bottom half so there's always room to count up.
+#[cfg(feature = "std")]
+const COUNTER_SEED_MASK: u64 = MAX_COUNTER >> 1;
+
+/// A version 7 (time ordered) UUID as defined by RFC 9562.
+///
+/// From most to least significant bit: a 48 bit timestamp in milliseconds since the unix epoch,
+/// the version (7), a 42 bit counter (split around the variant bits), and 32 random bits.
+///
+/// `UuidV7::new` keeps the timestamp, counter and a random number generator in a thread local (in
+/// the same spirit as `ProcessUniqueId`) so it never has to touch the OS random source after the
+/// first call on each thread. UUIDs created on one thread strictly increase, even within a
+/// millisecond or if the clock goes backwards. UUIDs from different threads (or processes) are
+/// told apart by the randomly seeded counter and random bits. Those bits come from a fast
+/// (splitmix64) generator that isn't cryptographically secure, so don't use these UUIDs as secrets.
+///
+/// With the `uuid` feature, these convert to and from `uuid::Uuid`.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct UuidV7(u128);
+
+#[cfg(feature = "std")]
+struct LocalState {
+    last_ms: u64,
+    counter: u64,
+    rng: u64,
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static LOCAL_STATE: Cell<Option<LocalState>> = const { Cell::new(None) };
+}
+
+#[cfg(feature = "std")]
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl UuidV7 {
+    /// Create a new UUID.
+    ///
+    /// **panics** if the OS random source can't be read (the first time this is called on each
+    /// thread).
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        LOCAL_STATE.with(|cell| {
+            let now = now();
+            let mut state = cell.take().unwrap_or_else(|| LocalState {
+                last_ms: 0,
+                counter: 0,
+                rng: getrandom::u64().expect("failed to read from the OS random source"),
+            });
+            if now > state.last_ms {
+                state.last_ms = now;
+                state.counter = splitmix64(&mut state.rng) & COUNTER_SEED_MASK;
+            } else if state.counter < MAX_COUNTER {
+                state.counter += 1;
+            } else {
+                // Out of counter for this millisecond; borrow the next one (RFC 9562, 6.2).
+                state.last_ms += 1;
+                state.counter = splitmix64(&mut state.rng) & COUNTER_SEED_MASK;
+            }
+            let uuid = UuidV7::from_fields(
+                state.last_ms,
+                state.counter,
+                splitmix64(&mut state.rng) as u32,
+            );
+            cell.set(Some(state));
+            uuid
+        })
+    }
+
+    #[cfg(feature = "std")]
+    fn from_fields(timestamp_ms: u64, counter: u64, random: u32) -> Self {
+        let counter = u128::from(counter);
+        UuidV7(
+            u128::from(timestamp_ms & 0xffff_ffff_ffff) << 80
+                | VERSION
+                | (counter >> 30) << 64
+                | VARIANT
+                | (counter & 0x3fff_ffff) << 32
+                | u128::from(random),
+        )
+    }
+
+    /// The time this UUID was created, in milliseconds since the unix epoch.
+    #[inline]
+    pub fn timestamp_ms(self) -> u64 {
+        (self.0 >> 80) as u64
+    }
+
+    /// The 128 bit value of this UUID.
+    #[inline]
+    pub fn as_u128(self) -> u128 {
+        self.0
+    }
+
+    /// Encode this UUID as 16 big endian bytes (the standard binary form).
+    #[inline]
+    pub fn to_bytes(self) -> [u8; 16] {
+        self.0.to_be_bytes()
+    }
+
+    /// Decode a UUID encoded with `to_bytes`.
+    ///
+    /// Returns `None` if the bytes don't hold a version 7, RFC 9562 variant UUID.
+    #[inline]
+    pub fn from_bytes(bytes: [u8; 16]) -> Option<Self> {
+        let value = u128::from_be_bytes(bytes);
+        if value & VERSION_MASK == VERSION && value & VARIANT_MASK == VARIANT {
+            Some(UuidV7(value))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for UuidV7 {
+    #[inline]
+    fn default() -> Self {
+        UuidV7::new()
+    }
+}
+
+impl fmt::Display for UuidV7 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let v = self.0;
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            v >> 96,
+            (v >> 80) & 0xffff,
+            (v >> 64) & 0xffff,
+            (v >> 48) & 0xffff,
+            v & 0xffff_ffff_ffff
+        )
+    }
+}
+
+/// An error returned when converting between this crate's IDs and `uuid::Uuid` fails.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum UuidConversionError {
+    /// The UUID has the wrong version.
+    WrongVersion,
+    /// The UUID isn't an RFC 9562 variant UUID.
+    WrongVariant,
+    /// The `ProcessUniqueId` prefix doesn't fit in the 58 bits a UUID has room for, or the
+    /// prefix stored in the UUID is `usize::MAX` or larger on this platform.
+    PrefixOverflow,
+}
+
+impl fmt::Display for UuidConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            UuidConversionError::WrongVersion => "wrong UUID version",
+            UuidConversionError::WrongVariant => "wrong UUID variant",
+            UuidConversionError::PrefixOverflow => "process unique ID prefix out of range",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UuidConversionError {}
+
+#[cfg(feature = "uuid")]
+impl From<UuidV7> for uuid::Uuid {
+    #[inline]
+    fn from(id: UuidV7) -> uuid::Uuid {
+        uuid::Uuid::from_u128(id.0)
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl core::convert::TryFrom<uuid::Uuid> for UuidV7 {
+    type Error = UuidConversionError;
+
+    fn try_from(uuid: uuid::Uuid) -> Result<Self, UuidConversionError> {
+        if uuid.get_version_num() != 7 {
+            Err(UuidConversionError::WrongVersion)
+        } else if uuid.get_variant() != uuid::Variant::RFC4122 {
+            Err(UuidConversionError::WrongVariant)
+        } else {
+            Ok(UuidV7(uuid.as_u128()))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::{UuidV7, LOCAL_STATE, MAX_COUNTER};
+    use std::thread;
+
+    #[test]
+    fn test_layout() {
+        let uuid = UuidV7::new();
+        let s = uuid.to_string();
+        assert_eq!(s.len(), 36);
+        assert_eq!(&s[14..15], "7");
+        assert!("89ab".contains(&s[19..20]));
+        assert_eq!(UuidV7::from_bytes(uuid.to_bytes()), Some(uuid));
+        assert_eq!(UuidV7::from_bytes([0; 16]), None);
+    }
+
+    #[test]
+    fn test_monotonic() {
+        let mut prev = UuidV7::new();
+        for _ in 0..10_000 {
+            let next = UuidV7::new();
+            assert!(next > prev);
+            prev = next;
+        }
+
+        // Running out of counter moves on to the next millisecond.
+        LOCAL_STATE.with(|cell| {
+            let mut state = cell.take().unwrap();
+            state.last_ms += 1000;
+            state.counter = MAX_COUNTER;
+            cell.set(Some(state));
+        });
+        let next = UuidV7::new();
+        assert!(next > prev);
+        assert_eq!(next.timestamp_ms(), prev.timestamp_ms() + 1001);
+    }
+
+    #[test]
+    fn test_threaded() {
+        let threads: Vec<_> = (0..4)
+            .map(|_| thread::spawn(|| (0..1000).map(|_| UuidV7::new()).collect::<Vec<_>>()))
+            .collect();
+        let mut results: Vec<_> = threads
+            .into_iter()
+            .flat_map(|t| t.join().unwrap())
+            .collect();
+        results.sort();
+        let old_len = results.len();
+        results.dedup();
+        assert_eq!(old_len, results.len());
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn test_uuid() {
+        use super::UuidConversionError;
+        use std::convert::TryFrom;
+        use uuid::Uuid;
+
+        let id = UuidV7::new();
+        let uuid = Uuid::from(id);
+        assert_eq!(uuid.get_version_num(), 7);
+        assert_eq!(uuid.to_string(), id.to_string());
+        assert_eq!(UuidV7::try_from(uuid), Ok(id));
+        assert_eq!(
+            UuidV7::try_from(Uuid::new_v4()),
+            Err(UuidConversionError::WrongVersion)
+        );
+    }
+}
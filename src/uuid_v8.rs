@@ -0,0 +1,137 @@
+// Copyright 2016 Steven Allen
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Embedding `ProcessUniqueId`s in version 8 (custom) UUIDs.
+//!
+//! A UUIDv8 has 122 bits to play with. We pack `prefix << 64 | offset` into them in order,
+//! skipping over the version and variant bits:
+//!
+//! ```text
+//!  0                   1                   2                   3
+//!  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+//! +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//! |                       prefix (bits 57-26)                     |
+//! +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//! |    prefix (bits 25-10)        |  ver  | prefix 9-0  |off 63-62|
+//! +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//! |var|                    offset (bits 61-32)                    |
+//! +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//! |                       offset (bits 31-0)                      |
+//! +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//! ```
+//!
+//! As the version and variant are fixed, the UUIDs compare in the same order as the IDs.
+use core::convert::TryFrom;
+
+use uuid::Uuid;
+
+use crate::process_unique_id::ProcessUniqueId;
+use crate::uuid_v7::UuidConversionError;
+
+const PREFIX_BITS: u32 = 58;
+
+/// Embed an ID in a version 8 UUID.
+///
+/// Fails with `UuidConversionError::PrefixOverflow` if the prefix needs more than 58 bits (which
+/// takes more than 2^58 threads to happen).
+impl TryFrom<ProcessUniqueId> for Uuid {
+    type Error = UuidConversionError;
+
+    fn try_from(id: ProcessUniqueId) -> Result<Uuid, UuidConversionError> {
+        let prefix = id.prefix() as u64;
+        if prefix >> PREFIX_BITS != 0 {
+            return Err(UuidConversionError::PrefixOverflow);
+        }
+        let packed = u128::from(prefix) << 64 | u128::from(id.offset());
+        Ok(Uuid::from_u128(
+            (packed >> 74) << 80
+                | 0x8 << 76
+                | ((packed >> 62) & 0xfff) << 64
+                | 0b10 << 62
+                | packed & ((1 << 62) - 1),
+        ))
+    }
+}
+
+/// Extract an ID embedded with `Uuid::try_from(ProcessUniqueId)`.
+///
+/// Any RFC 9562 variant, version 8 UUID decodes to some ID but only UUIDs made from
+/// `ProcessUniqueId`s decode to meaningful ones.
+impl TryFrom<Uuid> for ProcessUniqueId {
+    type Error = UuidConversionError;
+
+    fn try_from(uuid: Uuid) -> Result<ProcessUniqueId, UuidConversionError> {
+        if uuid.get_version_num() != 8 {
+            return Err(UuidConversionError::WrongVersion);
+        }
+        if uuid.get_variant() != uuid::Variant::RFC4122 {
+            return Err(UuidConversionError::WrongVariant);
+        }
+        let value = uuid.as_u128();
+        let packed = (value >> 80) << 74 | ((value >> 64) & 0xfff) << 62 | value & ((1 << 62) - 1);
+        ProcessUniqueId::try_from_parts((packed >> 64) as u64, packed as u64)
+            .ok_or(UuidConversionError::PrefixOverflow)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::process_unique_id::ProcessUniqueId;
+    use crate::uuid_v7::UuidConversionError;
+    use std::convert::TryFrom;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_round_trip() {
+        let mut ids = vec![
+            ProcessUniqueId::new(),
+            ProcessUniqueId::new(),
+            ProcessUniqueId::from_parts(0, 0),
+            ProcessUniqueId::from_parts(0, u64::MAX),
+            ProcessUniqueId::from_parts(1, 0),
+            ProcessUniqueId::from_parts(0x3ff, 0xc000_0000_0000_0000),
+            ProcessUniqueId::from_parts(((1u64 << 58) - 1) as usize, u64::MAX),
+        ];
+        let mut uuids = Vec::new();
+        for &id in &ids {
+            let uuid = Uuid::try_from(id).unwrap();
+            assert_eq!(uuid.get_version_num(), 8);
+            assert_eq!(uuid.get_variant(), uuid::Variant::RFC4122);
+            assert_eq!(ProcessUniqueId::try_from(uuid), Ok(id));
+            uuids.push(uuid);
+        }
+
+        ids.sort();
+        uuids.sort();
+        let decoded: Vec<_> = uuids
+            .into_iter()
+            .map(|uuid| ProcessUniqueId::try_from(uuid).unwrap())
+            .collect();
+        assert_eq!(decoded, ids);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            ProcessUniqueId::try_from(Uuid::new_v4()),
+            Err(UuidConversionError::WrongVersion)
+        );
+        // Same version, Microsoft variant.
+        let uuid = Uuid::try_from(ProcessUniqueId::new()).unwrap();
+        let microsoft = Uuid::from_u128(uuid.as_u128() | 0b11 << 62);
+        assert_eq!(
+            ProcessUniqueId::try_from(microsoft),
+            Err(UuidConversionError::WrongVariant)
+        );
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(
+            Uuid::try_from(ProcessUniqueId::from_parts(usize::MAX - 1, 0)),
+            Err(UuidConversionError::PrefixOverflow)
+        );
+    }
+}